@@ -0,0 +1,165 @@
+// Sprite sheets e clips de animação. Uma sheet é uma imagem dividida em quadros
+// do mesmo tamanho, numerados da esquerda para a direita e de cima para baixo.
+// Um clip escolhe quadros de uma sheet, quanto tempo cada um fica na tela e se
+// a animação repete. Os dois vêm do manifesto de assets:
+//
+//   [sheets]
+//   invaders = { file = "invaders.png", frame = [20, 20] }
+//
+//   [clips]
+//   invader1 = { sheet = "invaders", frames = [1, 0], durations = [0.9], loop = true }
+//
+// Com uma duração só, ela vale para todos os quadros.
+
+use ggez::graphics;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SheetEntry {
+    pub file: String,
+    pub frame: [u32; 2], // Largura e altura de cada quadro
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClipEntry {
+    pub sheet: String,
+    pub frames: Vec<u32>,
+    pub durations: Vec<f32>, // Segundos de cada quadro
+    #[serde(rename = "loop", default)]
+    pub looping: bool,
+}
+
+impl SheetEntry {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.frame[0] == 0 || self.frame[1] == 0 {
+            return Err(format!("O quadro da sheet {} precisa ter largura e altura", self.file));
+        }
+        return Ok(());
+    }
+}
+
+impl ClipEntry {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.frames.is_empty() {
+            return Err(String::from("precisa de pelo menos um quadro"));
+        }
+        if self.durations.len() != 1 && self.durations.len() != self.frames.len() {
+            return Err(String::from("precisa de uma duração só ou uma por quadro"));
+        }
+        if self.durations.iter().any(|duration| !duration.is_finite() || *duration <= 0.0) {
+            return Err(String::from("as durações precisam ser maiores que zero"));
+        }
+        return Ok(());
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SheetLayout { // Como a imagem se divide em quadros
+    frame: [u32; 2],
+    image: [u32; 2],
+    columns: u32,
+    rows: u32,
+}
+
+impl SheetLayout {
+    pub fn new(image_width: u32, image_height: u32, frame: [u32; 2]) -> Result<SheetLayout, String> {
+        // Sobras à direita e embaixo que não formam um quadro inteiro são ignoradas
+        let columns = image_width / frame[0].max(1);
+        let rows = image_height / frame[1].max(1);
+        if columns == 0 || rows == 0 {
+            return Err(format!("Imagem de {}x{} menor que um quadro de {}x{}", image_width, image_height, frame[0], frame[1]));
+        }
+        Ok(SheetLayout {
+            frame,
+            image: [image_width, image_height],
+            columns,
+            rows,
+        })
+    }
+
+    pub fn get_count(&self) -> u32 {
+        return self.columns * self.rows;
+    }
+
+    pub fn frame_rect(&self, index: u32) -> graphics::Rect { // Região do quadro em coordenadas de textura (0 a 1)
+        let index = index.min(self.get_count() - 1);
+        let width = self.frame[0] as f32 / self.image[0] as f32;
+        let height = self.frame[1] as f32 / self.image[1] as f32;
+        graphics::Rect::new(
+            (index % self.columns) as f32 * width,
+            (index / self.columns) as f32 * height,
+            width,
+            height,
+        )
+    }
+}
+
+
+pub struct SpriteSheet {
+    image: graphics::Image,
+    layout: SheetLayout,
+}
+
+impl SpriteSheet {
+    pub fn new(image: graphics::Image, frame: [u32; 2]) -> Result<SpriteSheet, String> {
+        let layout = SheetLayout::new(image.width(), image.height(), frame)?;
+        Ok(SpriteSheet { image, layout })
+    }
+
+    pub fn get_image(&self) -> &graphics::Image {
+        return &self.image;
+    }
+
+    pub fn get_layout(&self) -> &SheetLayout {
+        return &self.layout;
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    sheet: usize,        // Índice da sheet nas sheets carregadas
+    frames: Vec<u32>,
+    durations: Vec<f32>, // Uma por quadro
+    looping: bool,
+}
+
+impl Animation {
+    pub fn new(sheet: usize, entry: &ClipEntry) -> Animation {
+        let durations = if entry.durations.len() == 1 {
+            vec![entry.durations[0]; entry.frames.len()]
+        }
+        else {
+            entry.durations.clone()
+        };
+
+        Animation {
+            sheet,
+            frames: entry.frames.clone(),
+            durations,
+            looping: entry.looping,
+        }
+    }
+
+    pub fn get_sheet(&self) -> usize {
+        return self.sheet;
+    }
+
+    pub fn get_length(&self) -> f32 {
+        return self.durations.iter().sum();
+    }
+
+    pub fn frame_at(&self, time: f32) -> u32 { // Quadro da sheet que aparece depois de time segundos
+        let mut time = if self.looping { time % self.get_length() } else { time };
+
+        for (frame, duration) in self.frames.iter().zip(self.durations.iter()) {
+            if time < *duration {
+                return *frame;
+            }
+            time -= duration;
+        }
+        return *self.frames.last().unwrap_or(&0); // Sem loop fica parado no último quadro
+    }
+}
@@ -1,61 +1,263 @@
+// Os arquivos de cada asset vêm de um manifesto TOML que liga nomes lógicos a
+// arquivos (resources/assets.toml é o pacote padrão). Um tema é uma pasta em
+// /themes/<nome> com o seu próprio assets.toml; o que o tema não tiver, ou não
+// conseguir carregar, vem do pacote padrão. Os caminhos do manifesto são relativos
+// à pasta dele, ou à raiz dos resources quando começam com "/". Um clip do tema
+// pode usar uma sheet do pacote padrão, só para mudar o tempo de uma animação.
+//
+//   [sheets]                   nome -> file e frame, o tamanho de cada quadro
+//   [clips]                    player, player_death, invader1, invader2, invader3, shot,
+//                              ufo, explosion, cada um com sheet, frames, durations e loop
+//   [sounds]                   shot, player_hit, enemy_hit, ufo
+//   [font]                     file e size
+//   [colors]                   background e foreground, em [r, g, b]
+
 use ggez::graphics;
 use ggez::audio;
-use ggez::{Context, GameResult};
+use ggez::{Context, GameError, GameResult};
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use toml;
+
+use anm;
+use ecs;
+
+pub const MANIFEST_FILE: &str = "assets.toml";
+pub const THEMES_DIR: &str = "/themes";
+
+const CLIP_NAMES: [&str; 8] = ["player", "player_death", "invader1", "invader2", "invader3", "shot", "ufo", "explosion"];
+const SOUND_NAMES: [&str; 4] = ["shot", "player_hit", "enemy_hit", "ufo"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FontEntry {
+    pub file: String,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    pub background: Option<[u8; 3]>,
+    pub foreground: Option<[u8; 3]>, // Cor em que as sprites brancas e os textos são desenhados
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Manifest {
+    pub sheets: HashMap<String, anm::SheetEntry>,
+    pub clips: HashMap<String, anm::ClipEntry>,
+    pub sounds: HashMap<String, String>,
+    pub font: Option<FontEntry>,
+    pub colors: Colors,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let manifest: Manifest = toml::from_str(text).map_err(|e| format!("Manifesto de assets inválido: {}", e))?;
+
+        // Nomes desconhecidos são quase sempre erro de digitação
+        let unknown = manifest.clips.keys().find(|name| !CLIP_NAMES.contains(&name.as_str()))
+            .or_else(|| manifest.sounds.keys().find(|name| !SOUND_NAMES.contains(&name.as_str())));
+        if let Some(name) = unknown {
+            return Err(format!("Asset desconhecido no manifesto: {}", name));
+        }
+        for sheet in manifest.sheets.values() {
+            sheet.validate()?;
+        }
+        for (name, clip) in &manifest.clips {
+            clip.validate().map_err(|e| format!("O clip {} {}", name, e))?;
+        }
+        return Ok(manifest);
+    }
+}
+
+
+struct ClipSource {
+    clip: anm::ClipEntry,
+    sheet: Option<(String, [u32; 2])>, // Caminho da imagem e tamanho do quadro
+}
+
+struct Pack { // Manifesto e a pasta de onde os caminhos dele partem
+    dir: String,
+    manifest: Manifest,
+}
+
+impl Pack {
+    fn load(ctx: &mut Context, dir: &str) -> GameResult<Pack> {
+        let path = format!("{}/{}", dir, MANIFEST_FILE);
+        let mut text = String::new();
+        ctx.filesystem.open(&path)?.read_to_string(&mut text)?;
+        let manifest = Manifest::parse(&text).map_err(|e| GameError::ResourceLoadError(format!("{}: {}", path, e)))?;
+        Ok(Pack { dir: dir.to_string(), manifest })
+    }
+
+    fn resolve(&self, file: &str) -> String {
+        if file.starts_with('/') {
+            return file.to_string();
+        }
+        return format!("{}/{}", self.dir, file);
+    }
+
+    fn clip_source(&self, name: &str, default: &Pack) -> Option<ClipSource> { // A sheet vem deste pacote ou, se ele não tiver, do padrão
+        let clip = self.manifest.clips.get(name)?;
+        let sheet = self.manifest.sheets.get(&clip.sheet).map(|sheet| (self.resolve(&sheet.file), sheet.frame))
+            .or_else(|| default.manifest.sheets.get(&clip.sheet).map(|sheet| (default.resolve(&sheet.file), sheet.frame)));
+        Some(ClipSource { clip: clip.clone(), sheet })
+    }
+
+    fn sound_path(&self, name: &str) -> Option<String> {
+        return self.manifest.sounds.get(name).map(|file| self.resolve(file));
+    }
+}
+
+pub fn available_themes(ctx: &mut Context) -> Vec<String> { // Pastas de /themes que têm um manifesto
+    let dirs: Vec<_> = match ctx.filesystem.read_dir(THEMES_DIR) {
+        Ok(dirs) => dirs.collect(),
+        Err(_) => return Vec::new(),
+    };
+    let mut themes: Vec<String> = dirs.iter()
+        .filter(|dir| ctx.filesystem.is_file(dir.join(MANIFEST_FILE)))
+        .filter_map(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+        .collect();
+    themes.sort();
+    themes.dedup(); // A mesma pasta pode existir nos resources e na pasta do usuário
+    return themes;
+}
+
+fn load_with_fallback<S, T, F>(ctx: &mut Context, sources: (Option<S>, Option<S>), name: &str, mut load: F) -> GameResult<T>
+    where F: FnMut(&mut Context, &S) -> GameResult<T> { // Tenta o arquivo do tema e depois o do pacote padrão
+    let (theme_source, default_source) = sources;
+
+    if let Some(source) = theme_source {
+        match load(ctx, &source) {
+            Ok(asset) => return Ok(asset),
+            Err(e) => println!("Não foi possível carregar {} do tema, usando o padrão: {}", name, e),
+        }
+    }
+    let source = default_source.ok_or_else(|| GameError::ResourceLoadError(format!("O pacote padrão não tem o asset {}", name)))?;
+    load(ctx, &source)
+}
+
+fn load_sheet(ctx: &mut Context, sheets: &mut Vec<anm::SpriteSheet>, paths: &mut Vec<String>, path: &str, frame: [u32; 2]) -> GameResult<usize> {
+    // Vários clips costumam dividir a mesma imagem, que só é carregada uma vez
+    if let Some(index) = paths.iter().position(|loaded| loaded == path) {
+        return Ok(index);
+    }
+    let image = graphics::Image::new(ctx, path)?;
+    let sheet = anm::SpriteSheet::new(image, frame).map_err(|e| GameError::ResourceLoadError(format!("{}: {}", path, e)))?;
+    sheets.push(sheet);
+    paths.push(path.to_string());
+    Ok(sheets.len() - 1)
+}
+
+fn clip_name(clip: ecs::Clip) -> Option<String> {
+    match clip {
+        ecs::Clip::Player => Some(String::from("player")),
+        ecs::Clip::PlayerDeath => Some(String::from("player_death")),
+        ecs::Clip::Invader(3) => Some(String::from("invader3")),
+        ecs::Clip::Invader(2) => Some(String::from("invader2")),
+        ecs::Clip::Invader(_) => Some(String::from("invader1")),
+        ecs::Clip::Shot => Some(String::from("shot")),
+        ecs::Clip::Ufo => Some(String::from("ufo")),
+        ecs::Clip::Explosion => Some(String::from("explosion")),
+        ecs::Clip::Mask => None, // Desenhada a partir da máscara de dano
+    }
+}
+
+fn color(rgb: Option<[u8; 3]>) -> Option<graphics::Color> {
+    return rgb.map(|[r, g, b]| graphics::Color::from_rgb(r, g, b));
+}
 
-use go;
 
 pub struct Assets { // Classe que possui todos os assets necessários para o jogo
-    player_image: graphics::Image,
-    enemy_image_1: graphics::Image,
-    enemy_image_2: graphics::Image,
-    enemy_image_3: graphics::Image,
-    enemy_image_4: graphics::Image,
-    barrier_image_1: graphics::Image,
-    barrier_image_2: graphics::Image,
-    barrier_image_3: graphics::Image,
-    barrier_image_4: graphics::Image,
-    shot_image: graphics::Image,
+    sheets: Vec<anm::SpriteSheet>,
+    clips: HashMap<String, anm::Animation>,
     font: graphics::Font,
     shot_sound: audio::Source,
     player_hit_sound: audio::Source,
     enemy_hit_sound: audio::Source,
+    ufo_sound: audio::Source,
+    background: graphics::Color,
+    foreground: graphics::Color,
 }
 
 impl Assets { // Implementação dos métodos da classe de assets
-    pub fn new(ctx: &mut Context) -> GameResult<Assets> {
-        let player_image = graphics::Image::new(ctx, "/player.png")?;
-        let enemy_image_1 = graphics::Image::new(ctx, "/enemy1.png")?;
-        let enemy_image_2 = graphics::Image::new(ctx, "/enemy2.png")?;
-        let enemy_image_3 = graphics::Image::new(ctx, "/enemy3.png")?;
-        let enemy_image_4 = graphics::Image::new(ctx, "/enemy4.png")?;
-        let barrier_image_1 = graphics::Image::new(ctx, "/barrier1.png")?;
-        let barrier_image_2 = graphics::Image::new(ctx, "/barrier2.png")?;
-        let barrier_image_3 = graphics::Image::new(ctx, "/barrier3.png")?;
-        let barrier_image_4 = graphics::Image::new(ctx, "/barrier4.png")?;
-        let shot_image = graphics::Image::new(ctx, "/shot.png")?;
-        let font = graphics::Font::new(ctx, "/slkscr.ttf", 12)?;
-
-        let shot_sound = audio::Source::new(ctx, "/shoot.ogg")?;
-        let player_hit_sound = audio::Source::new(ctx, "/explosion.ogg")?;
-        let enemy_hit_sound = audio::Source::new(ctx, "/invaderkilled.ogg")?;
+    pub fn new(ctx: &mut Context, theme: Option<&str>) -> GameResult<Assets> {
+        let default = Pack::load(ctx, "")?; // Manifesto na raiz dos resources
+        let theme = match theme {
+            Some(name) => {
+                let dir = format!("{}/{}", THEMES_DIR, name);
+                if !ctx.filesystem.is_file(format!("{}/{}", dir, MANIFEST_FILE)) {
+                    let themes = available_themes(ctx);
+                    let available = if themes.is_empty() { String::from("nenhum") } else { themes.join(", ") };
+                    return Err(GameError::ResourceLoadError(format!("Tema {} não encontrado. Temas disponíveis: {}", name, available)));
+                }
+                Some(Pack::load(ctx, &dir)?)
+            }
+            None => None,
+        };
+
+        let clips = |name: &str| (theme.as_ref().and_then(|pack| pack.clip_source(name, &default)), default.clip_source(name, &default));
+        let sounds = |name: &str| (theme.as_ref().and_then(|pack| pack.sound_path(name)), default.sound_path(name));
+        let fonts = |pack: &Pack| pack.manifest.font.as_ref().map(|font| (pack.resolve(&font.file), font.size));
+        let sound = |ctx: &mut Context, path: &String| audio::Source::new(ctx, path);
+
+        let mut sheets = Vec::new();
+        let mut paths = Vec::new();
+        let mut animations = HashMap::new();
+        for name in CLIP_NAMES.iter() {
+            let animation = load_with_fallback(ctx, clips(name), name, |ctx, source: &ClipSource| {
+                let (ref path, frame) = *source.sheet.as_ref()
+                    .ok_or_else(|| GameError::ResourceLoadError(format!("A sheet {} não existe", source.clip.sheet)))?;
+                let index = load_sheet(ctx, &mut sheets, &mut paths, path, frame)?;
+                let count = sheets[index].get_layout().get_count();
+                if let Some(frame) = source.clip.frames.iter().find(|frame| **frame >= count) {
+                    return Err(GameError::ResourceLoadError(format!("{} tem só {} quadros, o clip pede o {}", path, count, frame)));
+                }
+                Ok(anm::Animation::new(index, &source.clip))
+            })?;
+            animations.insert(name.to_string(), animation);
+        }
+
+        let font_sources = (theme.as_ref().and_then(&fonts), fonts(&default));
+        let font = load_with_fallback(ctx, font_sources, "font", |ctx, &(ref path, size)| graphics::Font::new(ctx, path, size))?;
+
+        let shot_sound = load_with_fallback(ctx, sounds("shot"), "shot", sound)?;
+        let player_hit_sound = load_with_fallback(ctx, sounds("player_hit"), "player_hit", sound)?;
+        let enemy_hit_sound = load_with_fallback(ctx, sounds("enemy_hit"), "enemy_hit", sound)?;
+        let mut ufo_sound = load_with_fallback(ctx, sounds("ufo"), "ufo", sound)?;
+        ufo_sound.set_repeat(true); // Toca em loop enquanto o disco voador estiver na tela
+
+        let colors = |pick: fn(&Colors) -> Option<[u8; 3]>| {
+            color(theme.as_ref().and_then(|pack| pick(&pack.manifest.colors)).or_else(|| pick(&default.manifest.colors)))
+        };
+        let background = colors(|colors| colors.background).unwrap_or(graphics::BLACK);
+        let foreground = colors(|colors| colors.foreground).unwrap_or(graphics::WHITE);
+
         Ok(Assets {
-            player_image,
-            enemy_image_1,
-            enemy_image_2,
-            enemy_image_3,
-            enemy_image_4,
-            barrier_image_1,
-            barrier_image_2,
-            barrier_image_3,
-            barrier_image_4,
-            shot_image,
+            sheets,
+            clips: animations,
             font,
             shot_sound,
             player_hit_sound,
             enemy_hit_sound,
+            ufo_sound,
+            background,
+            foreground,
         })
     }
 
+    pub fn get_background(&self) -> graphics::Color {
+        return self.background;
+    }
+
+    pub fn get_foreground(&self) -> graphics::Color {
+        return self.foreground;
+    }
+
     pub fn get_font(&self) -> &graphics::Font {
         return &self.font;
     }
@@ -72,33 +274,18 @@ impl Assets { // Implementação dos métodos da classe de assets
         return &self.enemy_hit_sound;
     }
 
+    pub fn get_ufo_sound(&self) -> &audio::Source {
+        return &self.ufo_sound;
+    }
 
 
-    pub fn game_obj_sprite(&mut self, game_obj: &go::GameObj) -> &mut graphics::Image { // Seleciona a sprite correta para o obj
-        
-        let game_obj_tag = game_obj.get_tag();
+    pub fn get_animation(&self, clip: ecs::Clip) -> Option<&anm::Animation> {
+        return clip_name(clip).and_then(|name| self.clips.get(&name));
+    }
 
-        match game_obj_tag {
-            go::GameObjType::Player => &mut self.player_image,
-            go::GameObjType::Enemy => {
-                match game_obj.get_curr_sprite() {
-                    1 => &mut self.enemy_image_2,
-                    -1 => &mut self.enemy_image_1,
-                    2 => &mut self.enemy_image_4,
-                    -2 => &mut self.enemy_image_3,
-                    _ => &mut self.enemy_image_2 // O rust exige essa opção "_"
-                }
-            },
-            go::GameObjType::Barrier => {
-                match game_obj.get_curr_sprite()  {
-                    0 => &mut self.barrier_image_1,
-                    1 => &mut self.barrier_image_2,
-                    2 => &mut self.barrier_image_3,
-                    3 => &mut self.barrier_image_4,
-                    _ => &mut self.barrier_image_1 // O rust exige essa opção "_"
-                }
-            },
-            go::GameObjType::Shot => &mut self.shot_image,
-        }
+    pub fn clip_frame(&self, clip: ecs::Clip, time: f32) -> Option<(&graphics::Image, graphics::Rect)> { // Imagem e região do quadro que o clip mostra depois de time segundos
+        let animation = self.get_animation(clip)?;
+        let sheet = &self.sheets[animation.get_sheet()];
+        return Some((sheet.get_image(), sheet.get_layout().frame_rect(animation.frame_at(time))));
     }
-}
\ No newline at end of file
+}
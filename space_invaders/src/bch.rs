@@ -0,0 +1,80 @@
+// Benchmark da fase ampla das colisões: compara a grade uniforme com o teste de
+// todos os pares, com cada vez mais objetos. Os objetos são caixas do tamanho de
+// inimigos e tiros espalhadas numa área que cresce junto, como uma formação gigante.
+//
+// Rodar com: cargo run --release -- --bench-collisions
+
+use ggez::graphics::{Point2, Vector2};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use std::time::Instant;
+
+use col;
+
+const COUNTS: [usize; 6] = [50, 100, 500, 1000, 5000, 20000]; // Quantidade de inimigos em cada rodada
+const SHOTS_PER_ENEMY: usize = 4;  // Um tiro para cada 4 inimigos
+const SPACING: f32 = 36.0;         // Espaço médio entre inimigos, como na grade clássica
+const CELL_SIZE: f32 = 64.0;
+const REPEAT: u32 = 5;             // Repetições de cada medida, fica a menor
+
+fn random_boxes(rng: &mut XorShiftRng, count: usize, side: f32, size: Vector2) -> Vec<col::Aabb> {
+    return (0..count)
+        .map(|_| col::Aabb::new(Point2::new(rng.gen_range(0.0, side), rng.gen_range(0.0, side)), size))
+        .collect();
+}
+
+fn brute_force(shots: &[col::Aabb], targets: &[col::Aabb]) -> usize { // Testa todos os pares, como o collisions antigo
+    let mut pairs = 0;
+    for shot in shots {
+        for target in targets {
+            if shot.overlaps(target) {
+                pairs += 1;
+            }
+        }
+    }
+    return pairs;
+}
+
+fn with_grid(shots: &[col::Aabb], targets: &[col::Aabb]) -> usize { // Monta a grade e só testa as células vizinhas
+    let grid = col::Grid::build(CELL_SIZE, targets.iter().cloned());
+    let mut found = Vec::new();
+    let mut pairs = 0;
+    for shot in shots {
+        grid.query(shot, &mut found);
+        for &i in &found {
+            if shot.overlaps(&targets[i]) {
+                pairs += 1;
+            }
+        }
+    }
+    return pairs;
+}
+
+fn measure<F>(f: F) -> (usize, f64) where F: Fn() -> usize { // Menor tempo entre as repetições, em milissegundos
+    let mut best = f64::MAX;
+    let mut total = 0;
+    for _i in 0..REPEAT {
+        let start = Instant::now();
+        total += f(); // Somar todos os resultados impede o compilador de pular alguma repetição
+        best = best.min(start.elapsed().as_secs_f64() * 1000.0);
+    }
+    return (total / REPEAT as usize, best);
+}
+
+pub fn run() {
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]); // Sempre os mesmos objetos, para comparar execuções
+
+    println!("{:>8} {:>8} {:>14} {:>14} {:>10}", "inimigos", "tiros", "pares (ms)", "grade (ms)", "colisões");
+    for &count in COUNTS.iter() {
+        let side = (count as f32).sqrt() * SPACING;
+        let targets = random_boxes(&mut rng, count, side, Vector2::new(20.0, 14.0));
+        let shots = random_boxes(&mut rng, count / SHOTS_PER_ENEMY, side, Vector2::new(4.0, 12.0));
+
+        let (brute_pairs, brute_ms) = measure(|| brute_force(&shots, &targets));
+        let (grid_pairs, grid_ms) = measure(|| with_grid(&shots, &targets));
+
+        if brute_pairs != grid_pairs { // As duas fases precisam achar exatamente as mesmas colisões
+            println!("Resultado diferente com {} inimigos: {} pares, {} na grade", count, brute_pairs, grid_pairs);
+        }
+        println!("{:>8} {:>8} {:>14.3} {:>14.3} {:>10}", count, shots.len(), brute_ms, grid_ms, grid_pairs);
+    }
+}
@@ -0,0 +1,192 @@
+// Valores de balanceamento do jogo, lidos de um arquivo TOML.
+// Qualquer campo ausente no arquivo usa o valor padrão abaixo,
+// e campos desconhecidos são tratados como erro para pegar erros de digitação.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use toml;
+
+pub const DEFAULT_CONFIG_FILE: &str = "tuning.toml";
+pub const ENEMY_TYPES: usize = 3; // Tipos de inimigo, de '1' a '3' na formação
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difficulty { // Ajuste aplicado por cima do arquivo de configuração
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        return match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tuning {
+    pub player_hp: i32,
+    pub enemy_hp: i32,
+    pub shot_hp: i32,
+
+    pub player_hitbox: [f32; 2],    // [largura, altura] de cada hitbox, medidas nas sprites
+    pub enemy_hitbox: [f32; 2],
+    pub barrier_hitbox: [f32; 2],   // Também é o tamanho da máscara de dano da barreira
+    pub shot_hitbox: [f32; 2],
+    pub barrier_blast_radius: f32,  // Raio do buraco que um tiro abre na barreira
+
+    pub player_speed: f32,
+    pub enemy_speed: f32,           // Dividida pelo número de inimigos vivos
+    pub shot_speed: f32,
+    pub player_starting_pos_y: f32,
+
+    pub player_shot_time: f32,      // Tempo de espera entre tiros do player
+    pub enemy_shot_time: f32,       // Tempo de espera entre tiros dos inimigos
+    pub enemy_nline: i32,
+    pub enemy_ncolumn: i32,
+    pub max_diff_level: i32,        // A partir deste level os inimigos param de nascer mais baixo
+
+    pub enemy_points: Vec<i32>,     // Pontos de cada tipo de inimigo ('1' a '3' na formação)
+    pub level_score_multiplier: f32, // Pontos extras por level: pontos * (1 + level * multiplicador)
+
+    pub ufo_hp: i32,
+    pub ufo_hitbox: [f32; 2],
+    pub ufo_speed: f32,
+    pub ufo_pos_y: f32,             // Altura em que o disco voador cruza a tela
+    pub ufo_spawn_time: f32,        // Segundos entre aparições do disco voador
+    pub ufo_scores: Vec<i32>,       // Bônus possíveis, um deles é sorteado a cada acerto
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning {
+            player_hp: 3,
+            enemy_hp: 1,
+            shot_hp: 1,
+
+            player_hitbox: [32.0, 28.0],
+            enemy_hitbox: [20.0, 14.0],
+            barrier_hitbox: [32.0, 32.0],
+            shot_hitbox: [4.0, 12.0],
+            barrier_blast_radius: 3.0,
+
+            player_speed: 300.0,
+            enemy_speed: 600.0,
+            shot_speed: 300.0,
+            player_starting_pos_y: -290.0,
+
+            player_shot_time: 0.5,
+            enemy_shot_time: 1.0,
+            enemy_nline: 5,
+            enemy_ncolumn: 11,
+            max_diff_level: 7,
+
+            enemy_points: vec![10, 20, 30],
+            level_score_multiplier: 0.0,
+
+            ufo_hp: 1,
+            ufo_hitbox: [32.0, 14.0],
+            ufo_speed: 120.0,
+            ufo_pos_y: 285.0,
+            ufo_spawn_time: 20.0,
+            ufo_scores: vec![50, 100, 150, 300],
+        }
+    }
+}
+
+impl Tuning {
+    pub fn parse(text: &str) -> Result<Tuning, String> {
+        let tuning: Tuning = toml::from_str(text).map_err(|e| format!("Configuração inválida: {}", e))?;
+        tuning.validate()?;
+        return Ok(tuning);
+    }
+
+    pub fn load(path: &Path) -> Result<Tuning, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Não foi possível ler {}: {}", path.display(), e))?;
+        return Tuning::parse(&text).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> Tuning { // Levels com velocidade ou cadência próprias não são afetados
+        match difficulty {
+            Difficulty::Easy => {
+                self.player_hp += 2;
+                self.enemy_speed *= 0.75;
+                self.enemy_shot_time *= 1.5;
+            }
+            Difficulty::Normal => (),
+            Difficulty::Hard => {
+                self.player_hp = (self.player_hp - 1).max(1);
+                self.enemy_speed *= 1.25;
+                self.enemy_shot_time *= 0.6;
+            }
+        }
+        return self;
+    }
+
+    pub fn validate(&self) -> Result<(), String> { // Confere se os valores fazem sentido para o jogo
+        let hit_points = [
+            ("player_hp", self.player_hp),
+            ("enemy_hp", self.enemy_hp),
+            ("shot_hp", self.shot_hp),
+            ("ufo_hp", self.ufo_hp),
+        ];
+        for &(name, value) in hit_points.iter() {
+            if value < 1 {
+                return Err(format!("{} precisa ser pelo menos 1", name));
+            }
+        }
+
+        let positives = [
+            ("player_hitbox", self.player_hitbox[0].min(self.player_hitbox[1])),
+            ("enemy_hitbox", self.enemy_hitbox[0].min(self.enemy_hitbox[1])),
+            ("barrier_hitbox", self.barrier_hitbox[0].min(self.barrier_hitbox[1])),
+            ("shot_hitbox", self.shot_hitbox[0].min(self.shot_hitbox[1])),
+            ("barrier_blast_radius", self.barrier_blast_radius),
+            ("player_speed", self.player_speed),
+            ("enemy_speed", self.enemy_speed),
+            ("shot_speed", self.shot_speed),
+            ("player_shot_time", self.player_shot_time),
+            ("enemy_shot_time", self.enemy_shot_time),
+            ("ufo_hitbox", self.ufo_hitbox[0].min(self.ufo_hitbox[1])),
+            ("ufo_speed", self.ufo_speed),
+            ("ufo_spawn_time", self.ufo_spawn_time),
+        ];
+        for &(name, value) in positives.iter() {
+            if !(value > 0.0) || !value.is_finite() {
+                return Err(format!("{} precisa ser maior que zero", name));
+            }
+        }
+
+        if self.enemy_nline < 1 || self.enemy_ncolumn < 1 {
+            return Err(String::from("enemy_nline e enemy_ncolumn precisam ser pelo menos 1"));
+        }
+
+        if self.max_diff_level < 0 {
+            return Err(String::from("max_diff_level não pode ser negativo"));
+        }
+
+        if self.enemy_points.len() != ENEMY_TYPES || self.enemy_points.iter().any(|points| *points <= 0) {
+            return Err(format!("enemy_points precisa de um valor maior que zero para cada um dos {} tipos de inimigo", ENEMY_TYPES));
+        }
+
+        if !(self.level_score_multiplier >= 0.0) {
+            return Err(String::from("level_score_multiplier não pode ser negativo"));
+        }
+
+        if self.ufo_scores.is_empty() || self.ufo_scores.iter().any(|score| *score <= 0) {
+            return Err(String::from("ufo_scores precisa de pelo menos um bônus, todos maiores que zero"));
+        }
+
+        return Ok(());
+    }
+}
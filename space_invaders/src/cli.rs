@@ -0,0 +1,197 @@
+// Opções da linha de comando. Aqui só se lê e valida o texto dos argumentos,
+// carregar os arquivos (configuração, levels, replay) fica com o main.
+
+use std::path::PathBuf;
+
+use cfg;
+use vpt;
+
+pub const USAGE: &str = "\
+Best Space Invaders Ever
+
+Uso: space_invaders [opções]
+
+Janela:
+  --window <L>x<A>       Tamanho inicial da janela em pixels (padrão 480x640)
+  --fullscreen           Começa em tela cheia (F11 alterna durante o jogo)
+  --mute                 Desliga os sons
+  --theme <nome>         Tema de resources/themes (ex: high-contrast, retro-green)
+
+Partida:
+  --seed <número>        Seed da partida, para repetir o mesmo padrão de tiros
+  --level <número>       Level em que a partida começa (padrão 0)
+  --difficulty <nível>   easy, normal ou hard (padrão normal)
+  --config <arquivo>     Arquivo de balanceamento (padrão tuning.toml, se existir)
+  --levels <arquivo>     Pacote de levels no lugar da grade clássica
+
+Replays:
+  --record <arquivo>     Grava a partida
+  --replay <arquivo>     Reproduz uma partida gravada, com a seed e o level dela
+  --headless             Verifica o replay sem abrir janela (precisa de --replay)
+
+Outros:
+  --bench-collisions     Mede a fase ampla das colisões e sai
+  -h, --help             Mostra esta ajuda";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub window: (u32, u32),
+    pub fullscreen: bool,
+    pub mute: bool,
+    pub theme: Option<String>,      // Pasta em /themes, None para o pacote padrão
+    pub seed: Option<u64>,          // Sorteada quando não vem na linha de comando
+    pub level: i32,
+    pub difficulty: cfg::Difficulty,
+    pub config: Option<PathBuf>,
+    pub levels: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub headless: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            window: (vpt::WIDTH, vpt::HEIGHT),
+            fullscreen: false,
+            mute: false,
+            theme: None,
+            seed: None,
+            level: 0,
+            difficulty: cfg::Difficulty::Normal,
+            config: None,
+            levels: None,
+            record: None,
+            replay: None,
+            headless: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Play(Options),
+    BenchCollisions,
+    Help,
+}
+
+fn parse_window(value: &str) -> Option<(u32, u32)> { // Formato <largura>x<altura>, os dois maiores que zero
+    let mut parts = value.splitn(2, 'x');
+    let width = parts.next()?.parse::<u32>().ok()?;
+    let height = parts.next()?.parse::<u32>().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    return Some((width, height));
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> { // Recebe os argumentos sem o nome do programa
+    let mut options = Options::default();
+    let mut level_given = false; // level tem padrão, então não dá para saber pelo valor se veio na linha de comando
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let name = arg.as_str();
+        match name {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--bench-collisions" => return Ok(Command::BenchCollisions),
+            "--fullscreen" => options.fullscreen = true,
+            "--mute" => options.mute = true,
+            "--headless" => options.headless = true,
+            "--window" | "--theme" | "--seed" | "--level" | "--difficulty" | "--config" | "--levels" | "--record" | "--replay" => {
+                let value = args.next().ok_or_else(|| format!("Faltou o valor de {}", name))?;
+                let invalid = || format!("Valor inválido para {}: {}", name, value);
+                match name {
+                    "--window" => options.window = parse_window(value).ok_or_else(invalid)?,
+                    "--theme" if value.is_empty() || value.contains(['/', '\\']) || value.starts_with('.') => return Err(invalid()),
+                    "--theme" => options.theme = Some(value.clone()),
+                    "--seed" => options.seed = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                    "--level" => {
+                        options.level = value.parse::<i32>().ok().filter(|level| *level >= 0).ok_or_else(invalid)?;
+                        level_given = true;
+                    }
+                    "--difficulty" => options.difficulty = cfg::Difficulty::from_name(value).ok_or_else(invalid)?,
+                    "--config" => options.config = Some(PathBuf::from(value)),
+                    "--levels" => options.levels = Some(PathBuf::from(value)),
+                    "--record" => options.record = Some(PathBuf::from(value)),
+                    _ => options.replay = Some(PathBuf::from(value)),
+                }
+            }
+            _ => return Err(format!("Opção desconhecida: {} (use --help para ver as opções)", name)),
+        }
+    }
+
+    if options.record.is_some() && options.replay.is_some() {
+        return Err(String::from("Use --record ou --replay, não os dois"));
+    }
+    if options.replay.is_some() && (options.seed.is_some() || level_given) { // O replay já guarda a seed e o level inicial
+        return Err(String::from("--seed e --level não podem ser usados com --replay"));
+    }
+    if options.headless && options.replay.is_none() {
+        return Err(String::from("--headless precisa de --replay <arquivo>"));
+    }
+    return Ok(Command::Play(options));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        return parse(&args);
+    }
+
+    fn play(args: &[&str]) -> Options {
+        return match run(args) {
+            Ok(Command::Play(options)) => options,
+            other => panic!("{:?} não iniciou uma partida: {:?}", args, other),
+        };
+    }
+
+    #[test]
+    fn valid_flags_fill_the_options() {
+        assert_eq!(play(&[]), Options::default());
+
+        let options = play(&["--window", "960x1280", "--fullscreen", "--mute", "--theme", "retro-green",
+            "--seed", "42", "--level", "3", "--difficulty", "hard", "--config", "my_tuning.toml",
+            "--levels", "levels/example.toml", "--record", "match.replay"]);
+        assert_eq!(options.window, (960, 1280));
+        assert!(options.fullscreen && options.mute);
+        assert_eq!(options.theme, Some(String::from("retro-green")));
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.level, 3);
+        assert_eq!(options.difficulty, cfg::Difficulty::Hard);
+        assert_eq!(options.config, Some(PathBuf::from("my_tuning.toml")));
+        assert_eq!(options.levels, Some(PathBuf::from("levels/example.toml")));
+        assert_eq!(options.record, Some(PathBuf::from("match.replay")));
+
+        let options = play(&["--replay", "match.replay", "--headless"]);
+        assert_eq!(options.replay, Some(PathBuf::from("match.replay")));
+        assert!(options.headless);
+
+        assert_eq!(run(&["--help"]), Ok(Command::Help));
+        assert_eq!(run(&["--bench-collisions"]), Ok(Command::BenchCollisions));
+    }
+
+    #[test]
+    fn missing_or_invalid_values_are_errors() {
+        for name in &["--window", "--theme", "--seed", "--level", "--difficulty", "--config", "--levels", "--record", "--replay"] {
+            assert_eq!(run(&[name]), Err(format!("Faltou o valor de {}", name)));
+        }
+        for args in &[["--window", "960"], ["--window", "0x640"], ["--theme", "../x"], ["--seed", "-1"],
+                      ["--level", "-1"], ["--difficulty", "insane"]] {
+            assert!(run(args).is_err(), "{:?}", args);
+        }
+        assert!(run(&["--unknown"]).is_err());
+    }
+
+    #[test]
+    fn conflicting_flags_are_errors() {
+        assert!(run(&["--record", "a.replay", "--replay", "b.replay"]).is_err());
+        assert!(run(&["--headless"]).is_err());
+        assert!(run(&["--replay", "match.replay", "--seed", "42"]).is_err());
+        assert!(run(&["--replay", "match.replay", "--level", "0"]).is_err());
+        assert!(run(&["--level", "2", "--replay", "match.replay", "--headless"]).is_err());
+    }
+}
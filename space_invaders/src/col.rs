@@ -0,0 +1,221 @@
+// Formas de colisão dos objetos. Cada tipo de objeto tem uma caixa alinhada aos eixos (AABB)
+// declarada no tuning, medida a partir da sua sprite. Objetos com máscara de pixels,
+// como as barreiras, só colidem onde a máscara ainda está intacta.
+//
+// Objetos rápidos (os tiros) usam o teste contínuo em sweep, que procura o primeiro
+// instante do movimento em que eles encostam no alvo, então nenhum acerto é pulado.
+//
+// A grade uniforme no fim do arquivo é a fase ampla: ela separa os objetos por célula
+// para que cada teste só olhe os objetos próximos, e não todos os pares.
+//
+// Tudo em coordenadas do mundo: o centro da tela é (0, 0) e y cresce para cima.
+
+use ggez::graphics::{Point2, Vector2};
+use std::collections::HashMap;
+
+use msk;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point2,
+    pub max: Point2,
+}
+
+impl Aabb {
+    pub fn new(center: Point2, size: Vector2) -> Self { // Caixa de largura e altura "size" centrada no objeto
+        let half = size / 2.0;
+        Aabb {
+            min: center - half,
+            max: center + half,
+        }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        return self.min.x < other.max.x && other.min.x < self.max.x
+            && self.min.y < other.max.y && other.min.y < self.max.y;
+    }
+
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> { // Região comum às duas caixas
+        if !self.overlaps(other) {
+            return None;
+        }
+        return Some(Aabb {
+            min: Point2::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y)),
+            max: Point2::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y)),
+        });
+    }
+
+    pub fn translate(&self, delta: Vector2) -> Aabb {
+        return Aabb {
+            min: self.min + delta,
+            max: self.max + delta,
+        };
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb { // Menor caixa que contém as duas
+        return Aabb {
+            min: Point2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Point2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        };
+    }
+
+    pub fn contains(&self, point: Point2) -> bool {
+        return point.x >= self.min.x && point.x < self.max.x && point.y >= self.min.y && point.y < self.max.y;
+    }
+}
+
+
+pub enum Shape<'a> {
+    Box(Aabb),
+    Mask(Aabb, &'a msk::DamageMask), // A caixa cobre a máscara inteira, o pixel (0, 0) fica no canto superior esquerdo
+}
+
+impl<'a> Shape<'a> {
+    pub fn get_bounds(&self) -> Aabb {
+        return match *self {
+            Shape::Box(bounds) => bounds,
+            Shape::Mask(bounds, _) => bounds,
+        };
+    }
+
+    fn is_solid_at(&self, point: Point2) -> bool { // Se o ponto do mundo está dentro da parte sólida da forma
+        return match *self {
+            Shape::Box(bounds) => bounds.contains(point),
+            Shape::Mask(bounds, mask) => {
+                let x = (point.x - bounds.min.x).floor() as i32;
+                let y = (bounds.max.y - point.y).floor() as i32;
+                mask.is_solid(x, y)
+            }
+        };
+    }
+}
+
+
+pub fn overlaps(a: &Shape, b: &Shape) -> bool { // Teste de colisão entre duas formas quaisquer
+    let area = match a.get_bounds().intersection(&b.get_bounds()) {
+        Some(area) => area,
+        None => return false,
+    };
+
+    if let (&Shape::Box(_), &Shape::Box(_)) = (a, b) {
+        return true;
+    }
+
+    // Com máscara envolvida, procura um pixel sólido nas duas formas dentro da região comum
+    let mut y = area.min.y.floor() + 0.5;
+    while y < area.max.y {
+        let mut x = area.min.x.floor() + 0.5;
+        while x < area.max.x {
+            let point = Point2::new(x, y);
+            if a.is_solid_at(point) && b.is_solid_at(point) {
+                return true;
+            }
+            x += 1.0;
+        }
+        y += 1.0;
+    }
+    return false;
+}
+
+
+fn sweep_box(moving: &Aabb, delta: Vector2, target: &Aabb) -> Option<f32> { // Teste das faixas de cada eixo, contra uma caixa parada
+    let mut enter = 0.0f32;
+    let mut exit = 1.0f32;
+    let axes = [
+        (moving.min.x, moving.max.x, target.min.x, target.max.x, delta.x),
+        (moving.min.y, moving.max.y, target.min.y, target.max.y, delta.y),
+    ];
+
+    for &(min_a, max_a, min_b, max_b, d) in axes.iter() {
+        if d == 0.0 {
+            if !(min_a < max_b && min_b < max_a) { // Parado neste eixo e fora da faixa, nunca encosta
+                return None;
+            }
+        }
+        else {
+            let t0 = (min_b - max_a) / d;
+            let t1 = (max_b - min_a) / d;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if enter >= exit {
+                return None;
+            }
+        }
+    }
+    return Some(enter);
+}
+
+pub fn sweep(moving: &Aabb, delta: Vector2, target: &Shape) -> Option<f32> { // Fração do movimento (de 0 a 1) em que a caixa encosta no alvo pela primeira vez
+    return match *target {
+        Shape::Box(bounds) => sweep_box(moving, delta, &bounds),
+        Shape::Mask(bounds, _) => {
+            if !moving.union(&moving.translate(delta)).overlaps(&bounds) {
+                return None;
+            }
+
+            // Na máscara anda de pixel em pixel pelo caminho, para não atravessar buracos nem paredes finas
+            let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.0) as i32;
+            for k in 0..(steps + 1) {
+                let t = (k as f32) / (steps as f32);
+                if overlaps(&Shape::Box(moving.translate(delta * t)), target) {
+                    return Some(t);
+                }
+            }
+            None
+        }
+    };
+}
+
+
+pub struct Grid { // Grade uniforme com os índices dos objetos que tocam cada célula
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Grid {
+    pub fn new(cell_size: f32) -> Self {
+        Grid {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn build<I>(cell_size: f32, bounds: I) -> Self where I: Iterator<Item = Aabb> { // Grade com um índice para cada caixa, na ordem
+        let mut grid = Grid::new(cell_size);
+        for (index, b) in bounds.enumerate() {
+            grid.insert(index, &b);
+        }
+        return grid;
+    }
+
+    fn cell_range(&self, bounds: &Aabb) -> (i32, i32, i32, i32) { // Células cobertas pela caixa: x0, y0, x1, y1
+        return ((bounds.min.x / self.cell_size).floor() as i32,
+            (bounds.min.y / self.cell_size).floor() as i32,
+            (bounds.max.x / self.cell_size).floor() as i32,
+            (bounds.max.y / self.cell_size).floor() as i32);
+    }
+
+    pub fn insert(&mut self, index: usize, bounds: &Aabb) {
+        let (x0, y0, x1, y1) = self.cell_range(bounds);
+        for y in y0..(y1 + 1) {
+            for x in x0..(x1 + 1) {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    pub fn query(&self, bounds: &Aabb, found: &mut Vec<usize>) { // Índices que podem colidir com a caixa, em ordem crescente e sem repetição
+        found.clear();
+        let (x0, y0, x1, y1) = self.cell_range(bounds);
+        for y in y0..(y1 + 1) {
+            for x in x0..(x1 + 1) {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    found.extend_from_slice(cell);
+                }
+            }
+        }
+        // A ordem crescente mantém as colisões na mesma ordem do teste de todos os pares (e os replays iguais)
+        found.sort_unstable();
+        found.dedup();
+    }
+}
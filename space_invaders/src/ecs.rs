@@ -0,0 +1,284 @@
+// Modelo de entidades por componentes. Cada entidade é só um conjunto de componentes
+// opcionais, e os sistemas (em sys.rs) agem sobre as entidades que possuem os
+// componentes de que precisam. Um tipo novo de objeto é uma combinação nova de
+// componentes, sem precisar de mais um caso em cada match.
+
+use ggez::graphics::{Point2, Vector2};
+
+use col;
+use msk;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Faction { // Quem atira em quem: tiros só acertam entidades de outra facção
+    Player,
+    Invaders,
+    Neutral, // Atingida pelos dois lados, como as barreiras
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub current: Point2,
+    pub previous: Point2, // Posição antes do último movimento, usada no teste contínuo dos tiros
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Velocity {
+    pub direction: Vector2,
+    pub speed: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Collider {
+    pub hitbox: Vector2,                  // Largura e altura
+    pub mask: Option<msk::DamageMask>,    // Com máscara, só a parte intacta colide e os tiros a corroem
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    pub hit_points: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clip { // Qual animação desenhar, os quadros e tempos vêm do manifesto de assets
+    Player,
+    PlayerDeath,
+    Invader(i32), // Tipo do inimigo na formação
+    Shot,
+    Ufo,
+    Explosion,
+    Mask,         // Não é animação, é desenhada a partir da máscara do collider
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite {
+    pub clip: Clip,
+    pub time: f32, // Tempo de animação desde que a entidade nasceu
+    pub rate: f32, // Velocidade da animação, 1 é o tempo do manifesto
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Reward { // Pontos ganhos ao destruir a entidade
+    Points(i32),
+    RandomBonus, // Sorteado entre os ufo_scores do tuning
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Projectile {
+    pub damage: i32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Offscreen { // Some ao sair da tela, com essa folga em pixels
+    pub margin: f32,
+}
+
+// Componentes marcadores
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerControl { // Movida pelo input do jogador
+    pub slot: usize,       // Qual canhão do input controla esta entidade
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Formation;      // Marcha junto com a formação, atira e esmaga o que encosta
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Entity {
+    pub position: Option<Position>,
+    pub velocity: Option<Velocity>,
+    pub collider: Option<Collider>,
+    pub health: Option<Health>,
+    pub sprite: Option<Sprite>,
+    pub faction: Option<Faction>,
+    pub reward: Option<Reward>,
+    pub projectile: Option<Projectile>,
+    pub offscreen: Option<Offscreen>,
+    pub player_control: Option<PlayerControl>,
+    pub formation: Option<Formation>,
+}
+
+impl Entity {
+    pub fn get_pos(&self) -> Point2 { // Entidades sem posição ficam na origem
+        return match self.position {
+            Some(ref position) => position.current,
+            None => Point2::new(0.0, 0.0),
+        };
+    }
+
+    pub fn get_hit_points(&self) -> i32 {
+        return match self.health {
+            Some(ref health) => health.hit_points,
+            None => 0,
+        };
+    }
+
+    pub fn set_hit_points(&mut self, hit_points: i32) { // Nunca fica negativo
+        if let Some(ref mut health) = self.health {
+            health.hit_points = hit_points.max(0);
+        }
+    }
+
+    pub fn is_alive(&self) -> bool { // Entidades sem vida nunca morrem
+        return match self.health {
+            Some(ref health) => health.hit_points > 0,
+            None => true,
+        };
+    }
+
+    pub fn get_shape(&self) -> Option<col::Shape<'_>> { // Forma usada nas colisões, se a entidade tiver collider
+        let collider = self.collider.as_ref()?;
+        let bounds = col::Aabb::new(self.get_pos(), collider.hitbox);
+        return Some(match collider.mask {
+            Some(ref mask) => col::Shape::Mask(bounds, mask),
+            None => col::Shape::Box(bounds),
+        });
+    }
+
+    pub fn get_sweep(&self) -> Option<(col::Aabb, Vector2)> { // Caixa no começo do último movimento e o deslocamento feito
+        let collider = self.collider.as_ref()?;
+        let position = self.position.as_ref()?;
+        return Some((col::Aabb::new(position.previous, collider.hitbox), position.current - position.previous));
+    }
+
+    pub fn is_enemy_of(&self, other: &Entity) -> bool { // Se um tiro desta entidade pode acertar a outra
+        return match (self.faction, other.faction) {
+            (Some(a), Some(b)) => a != b || b == Faction::Neutral,
+            _ => false,
+        };
+    }
+}
+
+
+// Armazenamento das entidades. Cada entidade ganha um EntityId com o índice do
+// slot e a geração dele: quando a entidade é removida, a geração do slot aumenta e
+// os ids antigos param de valer, mesmo que o slot seja reaproveitado. Assim dá
+// para guardar um id entre frames sem medo de apontar para outra entidade.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId {
+    index: usize,
+    generation: u32,
+}
+
+impl EntityId {
+    pub fn get_index(&self) -> usize {
+        return self.index;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    entity: Option<Entity>, // None quando o slot está livre
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EntityStore {
+    slots: Vec<Slot>,
+    free: Vec<usize>, // Slots livres, reaproveitados antes de crescer o vetor
+}
+
+impl EntityStore {
+    pub fn new() -> EntityStore {
+        return EntityStore::default();
+    }
+
+    pub fn insert(&mut self, entity: Entity) -> EntityId {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.entity = Some(entity);
+            return EntityId { index, generation: slot.generation };
+        }
+        self.slots.push(Slot { generation: 0, entity: Some(entity) });
+        return EntityId { index: self.slots.len() - 1, generation: 0 };
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        if !self.contains(id) {
+            return None;
+        }
+        let slot = &mut self.slots[id.index];
+        slot.generation = slot.generation.wrapping_add(1); // Invalida todos os ids deste slot
+        self.free.push(id.index);
+        return slot.entity.take();
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        return self.get(id).is_some();
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        return match self.slots.get(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.entity.as_ref(),
+            _ => None,
+        };
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        return match self.slots.get_mut(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.entity.as_mut(),
+            _ => None,
+        };
+    }
+
+    pub fn get_id(&self, index: usize) -> Option<EntityId> { // Id da entidade que ocupa o slot agora
+        return match self.slots.get(index) {
+            Some(slot) if slot.entity.is_some() => Some(EntityId { index, generation: slot.generation }),
+            _ => None,
+        };
+    }
+
+    pub fn ids(&self) -> Vec<EntityId> { // Ids de todas as entidades, na ordem dos slots
+        return (0..self.slots.len()).filter_map(|index| self.get_id(index)).collect();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        return self.slots.iter().filter_map(|slot| slot.entity.as_ref());
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
+        return self.slots.iter_mut().filter_map(|slot| slot.entity.as_mut());
+    }
+
+    pub fn retain<F>(&mut self, mut keep: F) where F: FnMut(&Entity) -> bool { // Remove de uma vez tudo que não passar no teste
+        for id in self.ids() {
+            let remove = match self.get(id) {
+                Some(entity) => !keep(entity),
+                None => false,
+            };
+            if remove {
+                self.remove(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(hit_points: i32) -> Entity {
+        return Entity { health: Some(Health { hit_points }), ..Default::default() };
+    }
+
+    #[test]
+    fn stale_id_stops_resolving_after_slot_reuse() {
+        let mut entities = EntityStore::new();
+        let old = entities.insert(entity(1));
+        assert!(entities.remove(old).is_some());
+
+        let new = entities.insert(entity(2));
+        assert_eq!(old.get_index(), new.get_index()); // Mesmo slot, geração maior
+        assert!(new.generation > old.generation);
+
+        assert!(!entities.contains(old));
+        assert!(entities.get(old).is_none());
+        assert!(entities.get_mut(old).is_none());
+        assert!(entities.remove(old).is_none()); // O id antigo não remove a entidade nova
+
+        assert!(entities.contains(new));
+        assert_eq!(entities.get(new).unwrap().get_hit_points(), 2);
+        assert_eq!(entities.get_id(new.get_index()), Some(new));
+    }
+}
@@ -0,0 +1,27 @@
+// Eventos da partida. A simulação só registra o que aconteceu em cada passo e
+// quem estiver interessado (som, placar, pontuações flutuantes, ...) lê a fila
+// depois do step, cada um por conta própria. Assim a simulação não depende do ggez.
+
+use ggez::graphics::Point2;
+
+use ecs;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    EnemyKilled { pos: Point2, points: i32 },   // Pontos já com o multiplicador do level
+    PlayerHit { slot: usize, pos: Point2, hit_points: i32 }, // Canhão atingido, onde estava e as vidas que sobraram para ele
+    BarrierDamaged { pos: Point2 },             // Onde a barreira foi corroída
+    ShotFired { faction: ecs::Faction },
+    LevelCleared { level: i32 },                // Level que acabou de ser vencido
+    GameOver { score: i32, level: i32 },
+    TurnChanged { player: usize },              // No modo de dois jogadores, de quem é a vez agora
+}
+
+impl Event {
+    pub fn changes_hud(&self) -> bool { // Se o placar, o level ou as vidas mudaram
+        return match *self {
+            Event::EnemyKilled { .. } | Event::PlayerHit { .. } | Event::LevelCleared { .. } | Event::GameOver { .. } | Event::TurnChanged { .. } => true,
+            Event::BarrierDamaged { .. } | Event::ShotFired { .. } => false,
+        };
+    }
+}
@@ -1,229 +1,106 @@
 extern crate ggez;
-extern crate rand;
 
-const PLAYER_HP: i32 = 3;
-const ENEMY_HP: i32 = 1;
-const BARRIER_HP: i32 = 4;
-const SHOT_HP: i32 = 1;
-
-const PLAYER_SIZE: f32 = 12.0;
-const ENEMY_SIZE: f32 = 6.0;
-const BARRIER_SIZE: f32 = 12.0;
-const SHOT_SIZE: f32 = 6.0;
-
-const PLAYER_SPEED: f32 = 300.0;
-const ENEMY_SPEED: f32 = 600.0;
-const SHOT_SPEED: f32 = 300.0;
-const PLAYER_STARTING_POS_Y: f32 = -290.0;
+// Objetos do jogo montados a partir dos componentes de ecs.rs.
+// Cada função é um "molde" com os componentes daquele tipo de objeto.
 
 use ggez::graphics::{Point2, Vector2};
 use ggez::nalgebra as na;
 
-impl Movement for GameObj {
-    fn update_position(&mut self, time_var: f32){ // Atualiza a posição do objeto de acordo com a velocidade e direção
-        self.pos += self.direction * self.speed * time_var;
-    }   
-
-    fn get_pos(&self) -> Point2{
-        return self.pos;
-    }
-
-    fn get_pos_x(&self) -> f32{
-        return self.pos.x;
-    }
-
-    fn get_pos_y(&self) -> f32{
-        return self.pos.y;
-    }
-
-    fn set_pos(&mut self, pos: Point2){
-        self.pos = pos;
-    }
-
-    fn set_pos_x(&mut self, pos: f32){
-        
-        self.pos.x = pos;
-    }
-
-    fn set_pos_y(&mut self, pos: f32){
-        self.pos.y = pos;
-    }
-
-    fn get_speed(&self) -> f32{
-        return self.speed;
-    }
-
-    fn set_speed(&mut self, speed: f32){
-        self.speed = speed;
-    }
-
-    fn get_direction(&self) ->  Vector2{
-        return self.direction;
-    }
-
-    fn get_direction_x(&self) -> f32{
-        return self.direction.x;
-    }
-
-    fn get_direction_y(&self) -> f32{
-        return self.direction.y;
-    }
-
-    fn set_direction(&mut self, dir: Vector2){
-        self.direction = dir;
-    }
+use cfg;
+use ecs;
+use msk;
 
-    fn set_direction_x(&mut self, dir: f32){
-        self.direction.x = dir;
-    }
+pub const UFO_MARGIN: f32 = 20.0; // Distância fora da tela onde o disco voador nasce e some
 
-    fn set_direction_y(&mut self, dir: f32){
-        self.direction.y = dir;
-    }
+fn hitbox_size(size: [f32; 2]) -> Vector2 { // Hitbox do tuning, [largura, altura]
+    return Vector2::new(size[0], size[1]);
 }
 
-
-pub trait Movement {
-    fn update_position(&mut self, time_var: f32);
-    fn get_pos(&self) -> Point2;
-    fn get_pos_x(&self) -> f32;
-    fn get_pos_y(&self) -> f32;
-    fn set_pos(&mut self, pos: Point2);
-    fn set_pos_x(&mut self, pos: f32);
-    fn set_pos_y(&mut self, pos: f32);
-    fn get_speed(&self) -> f32;
-    fn set_speed(&mut self, speed: f32);
-    fn get_direction(&self) ->  Vector2;
-    fn get_direction_x(&self) -> f32;
-    fn get_direction_y(&self) -> f32;
-    fn set_direction(&mut self, dir: Vector2);
-    fn set_direction_x(&mut self, dir: f32);
-    fn set_direction_y(&mut self, dir: f32);
+fn position(pos: Point2) -> Option<ecs::Position> {
+    return Some(ecs::Position { current: pos, previous: pos });
 }
 
-
-// Valores possíveis assumidos pela struct "GameObj"
-
-#[derive(Debug)]
-pub enum GameObjType {
-    Player,
-    Enemy,
-    Barrier,
-    Shot,
+fn collider(size: [f32; 2]) -> Option<ecs::Collider> {
+    return Some(ecs::Collider { hitbox: hitbox_size(size), mask: None });
 }
 
-// Struct de um objeto de jogo genérico, funciona como uma classe
-
-#[derive(Debug)]
-pub struct GameObj {
-    tag: GameObjType,
-    curr_sprite: i32,
-    pos: Point2,
-    speed: f32,
-    direction: Vector2,
-    size: f32,
-    hit_points: i32,
+fn health(hit_points: i32) -> Option<ecs::Health> {
+    return Some(ecs::Health { hit_points });
 }
 
-
-// Implementação dos "métodos" da "classe" GameObj
-
-impl GameObj {
-    pub fn new(tag: GameObjType, pos: Point2, speed: f32, direction: Vector2, size: f32, hit_points: i32) -> Self {
-        GameObj{
-            tag: tag,               // Funciona como um ID
-            curr_sprite: 0,         // Sprite atual do objeto
-            pos: pos,               // Posição do objeto na tela
-            speed: speed,           // Velocidade do objeto
-            direction: direction,   // Direção em que o objeto está se movimentando
-            size: size,             // Tamanho da hitbox do objeto
-            hit_points: hit_points, // HP do objeto
-        }
-    }
-
-    pub fn new_player() -> Self { // construtor alternativo 
-        
-        return GameObj::new(GameObjType::Player, 
-            Point2::new(0.0, PLAYER_STARTING_POS_Y), 
-            PLAYER_SPEED, 
-            na::zero(),
-            PLAYER_SIZE,
-            PLAYER_HP);
-    }
-    pub fn new_shot(pos: Point2, direction: Vector2) -> Self { // construtor alternativo 
-        
-        return GameObj::new(GameObjType::Shot, 
-            pos, 
-            SHOT_SPEED, 
-            direction,
-            SHOT_SIZE,
-            SHOT_HP);
-    }
-
-     pub fn new_enemy(pos: Point2) -> Self { // construtor alternativo 
-        
-        return GameObj::new(GameObjType::Enemy, 
-            pos,
-            ENEMY_SPEED, 
-            Vector2::new(1.0, 0.0),
-            ENEMY_SIZE,
-            ENEMY_HP);
-    }
-
-    pub fn new_barrier(pos: Point2) -> Self { // construtor alternativo 
-        
-        return GameObj::new(GameObjType::Barrier,
-            pos,
-            0.0, 
-            na::zero(),
-            BARRIER_SIZE,
-            BARRIER_HP);
-    }
+fn sprite(clip: ecs::Clip) -> Option<ecs::Sprite> {
+    return Some(ecs::Sprite { clip, time: 0.0, rate: 1.0 });
+}
 
 
-    pub fn get_tag(&self) -> &GameObjType {
-        return &self.tag;
-    }
-    pub fn get_curr_sprite(&self) -> i32{
-        return self.curr_sprite;
-    }
-
-    pub fn set_curr_sprite(&mut self, curr_sprite: i32){
-        if curr_sprite < -2 {
-            self.curr_sprite = -2;
-        }
-        else if curr_sprite>3 {
-            self.curr_sprite = 3;
-        }
-        else{
-            self.curr_sprite = curr_sprite;
-        }
+pub fn new_player(pos_x: f32, slot: usize, tuning: &cfg::Tuning) -> ecs::Entity { // slot é o canhão do input que move o jogador
+    ecs::Entity {
+        position: position(Point2::new(pos_x, tuning.player_starting_pos_y)),
+        velocity: Some(ecs::Velocity { direction: na::zero(), speed: tuning.player_speed }),
+        collider: collider(tuning.player_hitbox),
+        health: health(tuning.player_hp),
+        sprite: sprite(ecs::Clip::Player),
+        faction: Some(ecs::Faction::Player),
+        player_control: Some(ecs::PlayerControl { slot }),
+        ..Default::default()
     }
+}
 
-    pub fn get_size(&self) -> f32{
-        return self.size;
+pub fn new_shot(pos: Point2, direction: Vector2, faction: ecs::Faction, tuning: &cfg::Tuning) -> ecs::Entity {
+    ecs::Entity {
+        position: position(pos),
+        velocity: Some(ecs::Velocity { direction, speed: tuning.shot_speed }),
+        collider: collider(tuning.shot_hitbox),
+        health: health(tuning.shot_hp),
+        sprite: sprite(ecs::Clip::Shot),
+        faction: Some(faction),
+        projectile: Some(ecs::Projectile { damage: 1 }),
+        offscreen: Some(ecs::Offscreen { margin: 0.0 }),
+        ..Default::default()
     }
+}
 
-    pub fn get_hit_points(&self) -> i32{
-        return self.hit_points;
+pub fn new_enemy(pos: Point2, enemy_type: i32, tuning: &cfg::Tuning) -> ecs::Entity { // enemy_type é o '1' a '3' da formação
+    ecs::Entity {
+        position: position(pos),
+        velocity: Some(ecs::Velocity { direction: Vector2::new(1.0, 0.0), speed: tuning.enemy_speed }),
+        collider: collider(tuning.enemy_hitbox),
+        health: health(tuning.enemy_hp),
+        sprite: sprite(ecs::Clip::Invader(enemy_type)),
+        faction: Some(ecs::Faction::Invaders),
+        reward: Some(ecs::Reward::Points(tuning.enemy_points[(enemy_type - 1) as usize])),
+        formation: Some(ecs::Formation),
+        ..Default::default()
     }
+}
 
-    pub fn set_hit_points(&mut self, hp: i32){
-        if hp < 0 {
-            self.hit_points = 0;
-        }
-        else if hp > 3 {
-            self.hit_points = 3;
-        }
-        else{
-            self.hit_points = hp;
-        }
+pub fn new_barrier(pos: Point2, tuning: &cfg::Tuning) -> ecs::Entity {
+    let width = tuning.barrier_hitbox[0].round() as usize;
+    let height = tuning.barrier_hitbox[1].round() as usize;
+
+    ecs::Entity {
+        position: position(pos),
+        collider: Some(ecs::Collider {
+            hitbox: hitbox_size(tuning.barrier_hitbox),
+            mask: Some(msk::DamageMask::new_shield(width, height)),
+        }),
+        health: health(1), // A barreira só morre quando a máscara fica vazia
+        sprite: sprite(ecs::Clip::Mask),
+        faction: Some(ecs::Faction::Neutral),
+        ..Default::default()
     }
+}
 
-
-    pub fn sub_hit_points(&mut self){
-        self.hit_points -= 1;
+pub fn new_ufo(pos: Point2, direction: Vector2, tuning: &cfg::Tuning) -> ecs::Entity {
+    ecs::Entity {
+        position: position(pos),
+        velocity: Some(ecs::Velocity { direction, speed: tuning.ufo_speed }),
+        collider: collider(tuning.ufo_hitbox),
+        health: health(tuning.ufo_hp),
+        sprite: sprite(ecs::Clip::Ufo),
+        faction: Some(ecs::Faction::Invaders),
+        reward: Some(ecs::Reward::RandomBonus),
+        offscreen: Some(ecs::Offscreen { margin: UFO_MARGIN }),
+        ..Default::default()
     }
-
-
 }
@@ -0,0 +1,149 @@
+// Tabela de recordes salva no diretório de dados do usuário.
+// Cada linha do arquivo guarda um recorde: <iniciais> <score> <level> <data>
+// Linhas inválidas são ignoradas, e um arquivo ausente vira uma tabela vazia.
+
+use ggez::Context;
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HIGH_SCORE_FILE: &str = "/highscores.txt";
+pub const MAX_ENTRIES: usize = 10;
+pub const INITIALS_LEN: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct HighScore {
+    initials: String,
+    score: i32,
+    level: i32,
+    date: String, // Data no formato AAAA-MM-DD
+}
+
+impl HighScore {
+    pub fn new(initials: &str, score: i32, level: i32, date: &str) -> Self {
+        HighScore {
+            initials: initials.to_string(),
+            score,
+            level,
+            date: date.to_string(),
+        }
+    }
+
+    pub fn get_initials(&self) -> &str {
+        return &self.initials;
+    }
+
+    pub fn get_score(&self) -> i32 {
+        return self.score;
+    }
+
+    pub fn get_level(&self) -> i32 {
+        return self.level;
+    }
+
+    pub fn get_date(&self) -> &str {
+        return &self.date;
+    }
+
+    fn parse(line: &str) -> Option<HighScore> { // Lê uma linha do arquivo, None se ela estiver corrompida
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 4 {
+            return None;
+        }
+
+        let initials = fields[0];
+        if initials.len() != INITIALS_LEN || !initials.chars().all(|c| c.is_ascii_uppercase()) {
+            return None;
+        }
+
+        let score = fields[1].parse::<i32>().ok()?;
+        let level = fields[2].parse::<i32>().ok()?;
+        return Some(HighScore::new(initials, score, level, fields[3]));
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct HighScoreTable {
+    entries: Vec<HighScore>, // Sempre ordenada do maior para o menor score
+}
+
+impl HighScoreTable {
+    pub fn parse(text: &str) -> Self {
+        let mut table = HighScoreTable::default();
+        for entry in text.lines().filter_map(HighScore::parse) {
+            table.insert(entry);
+        }
+        return table;
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&format!("{} {} {} {}\n", entry.initials, entry.score, entry.level, entry.date));
+        }
+        return text;
+    }
+
+    pub fn get_entries(&self) -> &Vec<HighScore> {
+        return &self.entries;
+    }
+
+    pub fn qualifies(&self, score: i32) -> bool { // Se o score entra na tabela
+        if score <= 0 {
+            return false;
+        }
+        return match self.entries.get(MAX_ENTRIES - 1) {
+            Some(last) => score > last.score,
+            None => true,
+        };
+    }
+
+    pub fn insert(&mut self, entry: HighScore) {
+        let pos = self.entries.iter().position(|e| entry.score > e.score).unwrap_or(self.entries.len());
+        self.entries.insert(pos, entry);
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
+
+
+pub fn load(ctx: &mut Context) -> HighScoreTable { // Carrega a tabela, qualquer erro resulta numa tabela vazia
+    let mut text = String::new();
+
+    match ctx.filesystem.open(HIGH_SCORE_FILE) {
+        Ok(mut file) => {
+            if let Err(e) = file.read_to_string(&mut text) {
+                println!("Não foi possível ler os recordes: {}", e);
+                text.clear();
+            }
+        }
+        Err(_) => (), // Arquivo ainda não existe
+    }
+    return HighScoreTable::parse(&text);
+}
+
+pub fn save(ctx: &mut Context, table: &HighScoreTable) {
+    let result = ctx.filesystem.create(HIGH_SCORE_FILE)
+        .and_then(|mut file| file.write_all(table.to_text().as_bytes()).map_err(|e| e.into()));
+
+    if let Err(e) = result {
+        println!("Não foi possível salvar os recordes: {}", e);
+    }
+}
+
+pub fn today() -> String { // Data atual (UTC) sem depender de bibliotecas externas
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+
+    // Conversão de dias desde 1970-01-01 para data civil (algoritmo de Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    return format!("{:04}-{:02}-{:02}", year, month, day);
+}
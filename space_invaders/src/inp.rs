@@ -0,0 +1,663 @@
+// Mapeamento das entradas (teclado e controle) para ações abstratas do jogo.
+// As ligações ficam num arquivo TOML na pasta do usuário e podem ser trocadas
+// na tela de controles. Cada entrada é um texto:
+//   nome da tecla no SDL                  "Left", "Space", "A"
+//   "pad:" e o nome do botão do controle  "pad:A", "pad:DPadLeft"
+//   "pad:" e um lado de um eixo           "pad:LeftX-", "pad:LeftX+"
+//
+// As entradas de controle dos canhões só valem para o controle de mesmo número
+// (o primeiro controle move o primeiro canhão), as dos menus valem para qualquer um.
+
+use ggez::Context;
+use ggez::event::{Axis, Button, Keycode};
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use toml;
+
+use sim;
+
+pub const CONTROLS_FILE: &str = "/controls.toml";
+const AXIS_DEADZONE: i16 = 8000; // Quanto o eixo precisa se mover para contar como apertado
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Left(usize), // Canhão que a ação controla
+    Right(usize),
+    Fire(usize),
+    Pause,
+    Up,          // Ações dos menus
+    Down,
+    Confirm,
+    Back,
+    TwoPlayers,  // Atalhos das telas
+    Coop,
+    CoopShared,
+    HighScores,
+    Controls,
+    Restart,
+    ResetControls,
+    Fullscreen,  // Valem em qualquer tela
+    PixelPerfect,
+}
+
+pub const ACTIONS: [Action; 20] = [ // Ordem em que as ações aparecem na tela de controles
+    Action::Left(0), Action::Right(0), Action::Fire(0),
+    Action::Left(1), Action::Right(1), Action::Fire(1),
+    Action::Pause, Action::Up, Action::Down, Action::Confirm, Action::Back,
+    Action::TwoPlayers, Action::Coop, Action::CoopShared, Action::HighScores, Action::Controls,
+    Action::Restart, Action::ResetControls, Action::Fullscreen, Action::PixelPerfect,
+];
+
+impl Action {
+    pub fn get_label(&self) -> String { // Nome mostrado na tela de controles
+        return match *self {
+            Action::Left(slot) => format!("Esquerda P{}", slot + 1),
+            Action::Right(slot) => format!("Direita P{}", slot + 1),
+            Action::Fire(slot) => format!("Atirar P{}", slot + 1),
+            Action::Pause => String::from("Pausar"),
+            Action::Up => String::from("Menu cima"),
+            Action::Down => String::from("Menu baixo"),
+            Action::Confirm => String::from("Confirmar"),
+            Action::Back => String::from("Voltar"),
+            Action::TwoPlayers => String::from("Dois jogadores"),
+            Action::Coop => String::from("Cooperativo"),
+            Action::CoopShared => String::from("Coop. vidas juntas"),
+            Action::HighScores => String::from("Recordes"),
+            Action::Controls => String::from("Controles"),
+            Action::Restart => String::from("Jogar de novo"),
+            Action::ResetControls => String::from("Restaurar controles"),
+            Action::Fullscreen => String::from("Tela cheia"),
+            Action::PixelPerfect => String::from("Pixel perfect"),
+        };
+    }
+
+    fn get_slot(&self) -> Option<usize> { // Canhão da ação, None para as ações dos menus
+        return match *self {
+            Action::Left(slot) | Action::Right(slot) | Action::Fire(slot) => Some(slot),
+            _ => None,
+        };
+    }
+
+    fn is_menu(&self) -> bool { // Ações das telas fora da partida
+        return matches!(*self, Action::Up | Action::Down | Action::Confirm | Action::Back
+            | Action::TwoPlayers | Action::Coop | Action::CoopShared | Action::HighScores
+            | Action::Controls | Action::Restart | Action::ResetControls);
+    }
+
+    fn is_global(&self) -> bool {
+        return matches!(*self, Action::Fullscreen | Action::PixelPerfect);
+    }
+
+    fn conflicts_with(&self, other: Action, input: Input) -> bool { // Se as duas ações disparariam juntas com a entrada
+        if self.is_global() || other.is_global() {
+            return true;
+        }
+        if let (Some(slot), Some(other_slot)) = (self.get_slot(), other.get_slot()) {
+            if slot != other_slot && input.is_pad() { // Cada canhão só ouve o seu controle
+                return false;
+            }
+        }
+        return self.is_menu() == other.is_menu();
+    }
+}
+
+
+const BUTTON_NAMES: [(Button, &str); 15] = [
+    (Button::A, "A"), (Button::B, "B"), (Button::X, "X"), (Button::Y, "Y"),
+    (Button::Back, "Back"), (Button::Guide, "Guide"), (Button::Start, "Start"),
+    (Button::LeftStick, "LeftStick"), (Button::RightStick, "RightStick"),
+    (Button::LeftShoulder, "LeftShoulder"), (Button::RightShoulder, "RightShoulder"),
+    (Button::DPadUp, "DPadUp"), (Button::DPadDown, "DPadDown"),
+    (Button::DPadLeft, "DPadLeft"), (Button::DPadRight, "DPadRight"),
+];
+
+const AXIS_NAMES: [(Axis, &str); 6] = [
+    (Axis::LeftX, "LeftX"), (Axis::LeftY, "LeftY"),
+    (Axis::RightX, "RightX"), (Axis::RightY, "RightY"),
+    (Axis::TriggerLeft, "TriggerLeft"), (Axis::TriggerRight, "TriggerRight"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(Keycode),
+    Button(Button),
+    Axis(Axis, bool), // Eixo e o lado dele, true para o positivo
+}
+
+impl Input {
+    pub fn parse(name: &str) -> Option<Input> {
+        if !name.starts_with("pad:") {
+            return Keycode::from_name(name).map(Input::Key);
+        }
+
+        let pad = &name[4..];
+        if let Some(&(button, _)) = BUTTON_NAMES.iter().find(|&&(_, button_name)| button_name == pad) {
+            return Some(Input::Button(button));
+        }
+        if pad.len() < 2 {
+            return None;
+        }
+        let (axis_name, side) = pad.split_at(pad.len() - 1);
+        let positive = match side {
+            "+" => true,
+            "-" => false,
+            _ => return None,
+        };
+        return AXIS_NAMES.iter()
+            .find(|&&(_, name)| name == axis_name)
+            .map(|&(axis, _)| Input::Axis(axis, positive));
+    }
+
+    pub fn get_name(&self) -> String {
+        return match *self {
+            Input::Key(keycode) => keycode.name(),
+            Input::Button(button) => {
+                let name = BUTTON_NAMES.iter().find(|&&(b, _)| b == button).map_or("?", |&(_, name)| name);
+                format!("pad:{}", name)
+            }
+            Input::Axis(axis, positive) => {
+                let name = AXIS_NAMES.iter().find(|&&(a, _)| a == axis).map_or("?", |&(_, name)| name);
+                format!("pad:{}{}", name, if positive { "+" } else { "-" })
+            }
+        };
+    }
+
+    pub fn is_pad(&self) -> bool { // Se a entrada vem de um controle
+        return match *self {
+            Input::Key(_) => false,
+            Input::Button(_) | Input::Axis(_, _) => true,
+        };
+    }
+}
+
+
+// Formato do arquivo. Campos ausentes usam as ligações padrão, as de cada
+// [[cannon]] vêm do canhão na mesma posição
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CannonFile {
+    left: Option<Vec<String>>,
+    right: Option<Vec<String>>,
+    fire: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ControlsFile {
+    pause: Option<Vec<String>>,
+    up: Option<Vec<String>>,
+    down: Option<Vec<String>>,
+    confirm: Option<Vec<String>>,
+    back: Option<Vec<String>>,
+    two_players: Option<Vec<String>>,
+    coop: Option<Vec<String>>,
+    coop_shared: Option<Vec<String>>,
+    high_scores: Option<Vec<String>>,
+    controls: Option<Vec<String>>,
+    restart: Option<Vec<String>>,
+    reset_controls: Option<Vec<String>>,
+    fullscreen: Option<Vec<String>>,
+    pixel_perfect: Option<Vec<String>>,
+    cannon: Vec<CannonFile>, // Um por canhão, precisa ficar no fim para o TOML
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    bindings: Vec<(Action, Vec<Input>)>, // Entradas de cada ação, na ordem de ACTIONS
+}
+
+fn cannon_defaults(slot: usize) -> Vec<(Action, Vec<Input>)> {
+    let keys = if slot == 0 {
+        [Keycode::Left, Keycode::Right, Keycode::Space]
+    }
+    else {
+        [Keycode::A, Keycode::D, Keycode::W]
+    };
+    return vec![
+        (Action::Left(slot), vec![Input::Key(keys[0]), Input::Button(Button::DPadLeft), Input::Axis(Axis::LeftX, false)]),
+        (Action::Right(slot), vec![Input::Key(keys[1]), Input::Button(Button::DPadRight), Input::Axis(Axis::LeftX, true)]),
+        (Action::Fire(slot), vec![Input::Key(keys[2]), Input::Button(Button::A)]),
+    ];
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut bindings = Vec::new();
+        for slot in 0..sim::MAX_CANNONS {
+            bindings.extend(cannon_defaults(slot));
+        }
+        bindings.push((Action::Pause, vec![Input::Key(Keycode::P), Input::Key(Keycode::Escape), Input::Button(Button::Start)]));
+        bindings.push((Action::Up, vec![Input::Key(Keycode::Up), Input::Button(Button::DPadUp)]));
+        bindings.push((Action::Down, vec![Input::Key(Keycode::Down), Input::Button(Button::DPadDown)]));
+        bindings.push((Action::Confirm, vec![Input::Key(Keycode::Return), Input::Button(Button::A)]));
+        bindings.push((Action::Back, vec![Input::Key(Keycode::Escape), Input::Button(Button::B)]));
+        bindings.push((Action::TwoPlayers, vec![Input::Key(Keycode::Num2)]));
+        bindings.push((Action::Coop, vec![Input::Key(Keycode::Num3)]));
+        bindings.push((Action::CoopShared, vec![Input::Key(Keycode::Num4)]));
+        bindings.push((Action::HighScores, vec![Input::Key(Keycode::H)]));
+        bindings.push((Action::Controls, vec![Input::Key(Keycode::C)]));
+        bindings.push((Action::Restart, vec![Input::Key(Keycode::R)]));
+        bindings.push((Action::ResetControls, vec![Input::Key(Keycode::Backspace)]));
+        bindings.push((Action::Fullscreen, vec![Input::Key(Keycode::F11)]));
+        bindings.push((Action::PixelPerfect, vec![Input::Key(Keycode::F10)]));
+        Bindings { bindings }
+    }
+}
+
+fn parse_inputs(names: &[String]) -> Result<Vec<Input>, String> {
+    return names.iter()
+        .map(|name| Input::parse(name).ok_or_else(|| format!("Entrada desconhecida nos controles: {}", name)))
+        .collect();
+}
+
+fn input_names(inputs: &[Input]) -> Vec<String> {
+    return inputs.iter().map(|input| input.get_name()).collect();
+}
+
+impl Bindings {
+    pub fn parse(text: &str) -> Result<Bindings, String> {
+        let file: ControlsFile = toml::from_str(text).map_err(|e| format!("Erro nos controles: {}", e))?;
+        if file.cannon.len() > sim::MAX_CANNONS {
+            return Err(format!("Erro nos controles: no máximo {} canhões", sim::MAX_CANNONS));
+        }
+
+        let mut fields = vec![
+            (Action::Pause, &file.pause),
+            (Action::Up, &file.up),
+            (Action::Down, &file.down),
+            (Action::Confirm, &file.confirm),
+            (Action::Back, &file.back),
+            (Action::TwoPlayers, &file.two_players),
+            (Action::Coop, &file.coop),
+            (Action::CoopShared, &file.coop_shared),
+            (Action::HighScores, &file.high_scores),
+            (Action::Controls, &file.controls),
+            (Action::Restart, &file.restart),
+            (Action::ResetControls, &file.reset_controls),
+            (Action::Fullscreen, &file.fullscreen),
+            (Action::PixelPerfect, &file.pixel_perfect),
+        ];
+        for (slot, cannon) in file.cannon.iter().enumerate() {
+            fields.push((Action::Left(slot), &cannon.left));
+            fields.push((Action::Right(slot), &cannon.right));
+            fields.push((Action::Fire(slot), &cannon.fire));
+        }
+
+        let mut bindings = Bindings::default(); // Já vem com cannon_defaults de cada canhão
+        for (action, names) in fields {
+            if let Some(ref names) = *names {
+                bindings.set_all(action, parse_inputs(names)?);
+            }
+        }
+        return Ok(bindings);
+    }
+
+    fn to_file(&self) -> ControlsFile {
+        let names = |action| input_names(self.get(action));
+        ControlsFile {
+            pause: Some(names(Action::Pause)),
+            up: Some(names(Action::Up)),
+            down: Some(names(Action::Down)),
+            confirm: Some(names(Action::Confirm)),
+            back: Some(names(Action::Back)),
+            two_players: Some(names(Action::TwoPlayers)),
+            coop: Some(names(Action::Coop)),
+            coop_shared: Some(names(Action::CoopShared)),
+            high_scores: Some(names(Action::HighScores)),
+            controls: Some(names(Action::Controls)),
+            restart: Some(names(Action::Restart)),
+            reset_controls: Some(names(Action::ResetControls)),
+            fullscreen: Some(names(Action::Fullscreen)),
+            pixel_perfect: Some(names(Action::PixelPerfect)),
+            cannon: (0..sim::MAX_CANNONS)
+                .map(|slot| CannonFile {
+                    left: Some(names(Action::Left(slot))),
+                    right: Some(names(Action::Right(slot))),
+                    fire: Some(names(Action::Fire(slot))),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        return toml::to_string(&self.to_file()).unwrap_or_default();
+    }
+
+    pub fn get(&self, action: Action) -> &[Input] {
+        return match self.bindings.iter().find(|&&(a, _)| a == action) {
+            Some((_, inputs)) => inputs,
+            None => &[],
+        };
+    }
+
+    fn set_all(&mut self, action: Action, inputs: Vec<Input>) {
+        if let Some(&mut (_, ref mut current)) = self.bindings.iter_mut().find(|&&mut (a, _)| a == action) {
+            *current = inputs;
+        }
+    }
+
+    pub fn bind(&mut self, action: Action, input: Input) -> bool { // Troca a entrada do mesmo tipo (tecla ou controle) da ação, false se recusou
+        let mut bindings = self.bindings.clone();
+        let mut freed = Vec::new(); // Entradas que a ação larga, para dar a quem perder a nova
+        if let Some(&mut (_, ref mut inputs)) = bindings.iter_mut().find(|&&mut (a, _)| a == action) {
+            freed = inputs.iter().cloned().filter(|i| i.is_pad() == input.is_pad() && *i != input).collect();
+            inputs.retain(|i| i.is_pad() != input.is_pad());
+            inputs.insert(0, input);
+        }
+
+        // A entrada sai das ações que disparariam junto com esta. Quem ficar sem nenhuma
+        // entrada do tipo troca com a ação, e se nenhuma servir a troca é recusada
+        for index in 0..bindings.len() {
+            let other = bindings[index].0;
+            if other == action || !other.conflicts_with(action, input) || !bindings[index].1.contains(&input) {
+                continue;
+            }
+            bindings[index].1.retain(|&i| i != input);
+            if bindings[index].1.iter().any(|i| i.is_pad() == input.is_pad()) {
+                continue;
+            }
+
+            let swap = freed.iter().position(|&old| {
+                !bindings.iter().any(|&(a, ref inputs)| a != other && a.conflicts_with(other, old) && inputs.contains(&old))
+            });
+            match swap {
+                Some(position) => {
+                    let old = freed.remove(position);
+                    bindings[index].1.insert(0, old);
+                }
+                None => return false,
+            }
+        }
+
+        self.bindings = bindings;
+        return true;
+    }
+
+    pub fn actions(&self, input: Input, pad: Option<usize>) -> Vec<Action> { // Ações disparadas pela entrada, pad é o número do controle
+        return self.bindings.iter()
+            .filter(|&&(action, ref inputs)| {
+                let right_pad = match (action.get_slot(), pad) {
+                    (Some(slot), Some(pad)) => slot == pad,
+                    _ => true,
+                };
+                right_pad && inputs.contains(&input)
+            })
+            .map(|&(action, _)| action)
+            .collect();
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct HeldActions { // Ações dos canhões que estão seguradas, resolvidas no input de cada frame
+    held: Vec<(Action, Input, Option<usize>)>, // Ação, entrada e controle que a segura, na ordem em que foram apertadas
+}
+
+impl HeldActions {
+    pub fn press(&mut self, action: Action, input: Input, pad: Option<usize>) { // A repetição da tecla não duplica a entrada
+        if action.get_slot().is_some() && !self.held.contains(&(action, input, pad)) {
+            self.held.push((action, input, pad));
+        }
+    }
+
+    pub fn release(&mut self, input: Input, pad: Option<usize>) { // Solta tudo que a entrada segurava
+        self.held.retain(|&(_, i, p)| i != input || p != pad);
+    }
+
+    pub fn to_input_state(&self) -> sim::InputState {
+        let mut state = sim::InputState::default();
+
+        for &(action, _, _) in &self.held { // Com as duas direções seguradas vale a última apertada
+            match action {
+                Action::Left(slot) if slot < sim::MAX_CANNONS => state.cannons[slot].xaxis = -1.0,
+                Action::Right(slot) if slot < sim::MAX_CANNONS => state.cannons[slot].xaxis = 1.0,
+                Action::Fire(slot) if slot < sim::MAX_CANNONS => state.cannons[slot].is_firing = true,
+                _ => (),
+            }
+        }
+        return state;
+    }
+}
+
+
+#[derive(Debug, Default)]
+pub struct AxisTracker { // Transforma o movimento contínuo dos eixos em apertar e soltar
+    sides: HashMap<(usize, Axis), i8>, // Lado em que cada eixo de cada controle está: -1, 0 ou 1
+}
+
+impl AxisTracker {
+    pub fn update(&mut self, pad: usize, axis: Axis, value: i16) -> Vec<(Input, bool)> { // Entradas soltas (false) e apertadas (true)
+        let side = if value > AXIS_DEADZONE {
+            1
+        }
+        else if value < -AXIS_DEADZONE {
+            -1
+        }
+        else {
+            0
+        };
+
+        let previous = self.sides.insert((pad, axis), side).unwrap_or(0);
+        let mut changes = Vec::new();
+        if previous != side {
+            if previous != 0 {
+                changes.push((Input::Axis(axis, previous > 0), false));
+            }
+            if side != 0 {
+                changes.push((Input::Axis(axis, side > 0), true));
+            }
+        }
+        return changes;
+    }
+}
+
+
+pub fn load(ctx: &mut Context) -> Bindings { // Carrega os controles, qualquer erro resulta nos padrões
+    let mut text = String::new();
+
+    match ctx.filesystem.open(CONTROLS_FILE) {
+        Ok(mut file) => {
+            if let Err(e) = file.read_to_string(&mut text) {
+                println!("Não foi possível ler os controles: {}", e);
+                return Bindings::default();
+            }
+        }
+        Err(_) => return Bindings::default(), // Arquivo ainda não existe
+    }
+
+    return match Bindings::parse(&text) {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            println!("{}", e);
+            Bindings::default()
+        }
+    };
+}
+
+pub fn save(ctx: &mut Context, bindings: &Bindings) {
+    let result = ctx.filesystem.create(CONTROLS_FILE)
+        .and_then(|mut file| file.write_all(bindings.to_text().as_bytes()).map_err(|e| e.into()));
+
+    if let Err(e) = result {
+        println!("Não foi possível salvar os controles: {}", e);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: Input = Input::Key(Keycode::Left);
+    const RIGHT: Input = Input::Key(Keycode::Right);
+
+    fn xaxis(held: &HeldActions, slot: usize) -> f32 {
+        return held.to_input_state().cannons[slot].xaxis;
+    }
+
+    #[test]
+    fn releasing_one_direction_keeps_the_other() {
+        let mut held = HeldActions::default();
+        held.press(Action::Left(0), LEFT, None);
+        held.press(Action::Right(0), RIGHT, None);
+        held.release(RIGHT, None);
+        assert_eq!(xaxis(&held, 0), -1.0);
+
+        held.press(Action::Right(0), RIGHT, None);
+        held.release(LEFT, None);
+        assert_eq!(xaxis(&held, 0), 1.0);
+
+        held.release(RIGHT, None);
+        assert_eq!(xaxis(&held, 0), 0.0);
+    }
+
+    #[test]
+    fn last_pressed_direction_wins() {
+        let mut held = HeldActions::default();
+        held.press(Action::Right(0), RIGHT, None);
+        held.press(Action::Left(0), LEFT, None);
+        assert_eq!(xaxis(&held, 0), -1.0);
+
+        held.release(LEFT, None);
+        held.press(Action::Left(0), LEFT, None);
+        held.release(RIGHT, None);
+        held.press(Action::Right(0), RIGHT, None);
+        assert_eq!(xaxis(&held, 0), 1.0);
+    }
+
+    #[test]
+    fn key_repeat_is_released_at_once() {
+        let mut held = HeldActions::default();
+        held.press(Action::Left(0), LEFT, None);
+        held.press(Action::Left(0), LEFT, None);
+        held.release(LEFT, None);
+        assert_eq!(xaxis(&held, 0), 0.0);
+    }
+
+    #[test]
+    fn release_without_press_is_ignored() {
+        let mut held = HeldActions::default();
+        held.release(LEFT, None);
+        held.press(Action::Right(0), RIGHT, None);
+        held.release(LEFT, None);
+        assert_eq!(xaxis(&held, 0), 1.0);
+    }
+
+    #[test]
+    fn key_and_pad_hold_the_same_direction() {
+        let mut held = HeldActions::default();
+        let dpad = Input::Button(Button::DPadLeft);
+        held.press(Action::Left(0), LEFT, None);
+        held.press(Action::Left(0), dpad, Some(0));
+        held.release(LEFT, None);
+        assert_eq!(xaxis(&held, 0), -1.0);
+
+        held.release(dpad, Some(1)); // O mesmo botão em outro controle não solta este
+        assert_eq!(xaxis(&held, 0), -1.0);
+        held.release(dpad, Some(0));
+        assert_eq!(xaxis(&held, 0), 0.0);
+    }
+
+    #[test]
+    fn cannons_and_fire_are_independent() {
+        let mut held = HeldActions::default();
+        let fire = Input::Key(Keycode::Space);
+        held.press(Action::Left(0), LEFT, None);
+        held.press(Action::Right(1), Input::Key(Keycode::D), None);
+        held.press(Action::Fire(0), fire, None);
+
+        let state = held.to_input_state();
+        assert_eq!(state.cannons[0].xaxis, -1.0);
+        assert_eq!(state.cannons[1].xaxis, 1.0);
+        assert!(state.cannons[0].is_firing);
+        assert!(!state.cannons[1].is_firing);
+
+        held.release(fire, None);
+        held.release(LEFT, None);
+        let state = held.to_input_state();
+        assert!(!state.cannons[0].is_firing);
+        assert_eq!(state.cannons[0].xaxis, 0.0);
+        assert_eq!(state.cannons[1].xaxis, 1.0);
+    }
+
+    #[test]
+    fn menu_actions_are_not_held() {
+        let mut held = HeldActions::default();
+        held.press(Action::Pause, Input::Key(Keycode::P), None);
+        assert_eq!(held.to_input_state(), sim::InputState::default());
+    }
+
+    #[test]
+    fn held_bindings_resolve_through_the_axis_tracker() {
+        let bindings = Bindings::default();
+        let mut axes = AxisTracker::default();
+        let mut held = HeldActions::default();
+
+        for &value in [-20000, 20000, 0].iter() {
+            for (input, pressed) in axes.update(0, Axis::LeftX, value) {
+                if pressed {
+                    for action in bindings.actions(input, Some(0)) {
+                        held.press(action, input, Some(0));
+                    }
+                }
+                else {
+                    held.release(input, Some(0));
+                }
+            }
+            let expected = (value / 20000) as f32;
+            assert_eq!(xaxis(&held, 0), expected);
+            assert_eq!(xaxis(&held, 1), 0.0);
+        }
+    }
+
+    #[test]
+    fn partial_second_cannon_keeps_its_own_defaults() {
+        let bindings = Bindings::parse("[[cannon]]\n\n[[cannon]]\nfire = [\"pad:X\"]\n").unwrap();
+        let defaults = Bindings::default();
+
+        assert_eq!(bindings.get(Action::Fire(1)), &[Input::Button(Button::X)]);
+        assert_eq!(bindings.get(Action::Left(1)), defaults.get(Action::Left(1)));
+        assert_eq!(bindings.get(Action::Right(1)), defaults.get(Action::Right(1)));
+        assert_eq!(bindings.get(Action::Left(0)), defaults.get(Action::Left(0)));
+        assert_eq!(bindings.get(Action::Fire(0)), defaults.get(Action::Fire(0)));
+    }
+
+    #[test]
+    fn binding_a_used_key_swaps_the_keys() {
+        let mut bindings = Bindings::default();
+        assert!(bindings.bind(Action::Back, Input::Key(Keycode::Return)));
+
+        assert_eq!(bindings.get(Action::Back), &[Input::Key(Keycode::Return), Input::Button(Button::B)]);
+        assert_eq!(bindings.get(Action::Confirm), &[Input::Key(Keycode::Escape), Input::Button(Button::A)]);
+        assert_eq!(bindings.get(Action::Pause), Bindings::default().get(Action::Pause)); // Outro grupo, não troca
+    }
+
+    #[test]
+    fn global_actions_take_the_input_from_every_group() {
+        let mut bindings = Bindings::default();
+        assert!(bindings.bind(Action::Fire(0), Input::Key(Keycode::F10)));
+
+        assert_eq!(bindings.get(Action::Fire(0)), &[Input::Key(Keycode::F10), Input::Button(Button::A)]);
+        assert_eq!(bindings.get(Action::PixelPerfect), &[Input::Key(Keycode::Space)]);
+    }
+
+    #[test]
+    fn binding_that_leaves_an_action_without_input_is_refused() {
+        let mut bindings = Bindings::default();
+        assert!(!bindings.bind(Action::Fullscreen, Input::Button(Button::A))); // Não tem botão para dar em troca
+        assert_eq!(bindings, Bindings::default());
+    }
+
+    #[test]
+    fn pad_inputs_of_the_other_cannon_are_kept() {
+        let mut bindings = Bindings::default();
+        assert!(bindings.bind(Action::Fire(0), Input::Button(Button::DPadLeft)));
+
+        assert_eq!(bindings.get(Action::Left(0)), &[Input::Key(Keycode::Left), Input::Axis(Axis::LeftX, false)]);
+        assert_eq!(bindings.get(Action::Left(1)), Bindings::default().get(Action::Left(1)));
+    }
+}
@@ -0,0 +1,220 @@
+// Definição dos levels: formação dos inimigos, barreiras, altura inicial,
+// velocidade e cadência de tiro. Um pacote de levels é lido de um arquivo TOML
+// com uma tabela [[level]] por level, jogados em ordem. Depois do último,
+// o último level se repete.
+//
+// Na formação cada caractere é uma célula: '.' vazio, '1' a '3' são os tipos de inimigo.
+
+use ggez::graphics::Point2;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use toml;
+
+use cfg;
+use ecs;
+use go;
+
+const FORMATION_MARGIN: f32 = 40.0; // Espaço livre nas laterais da formação
+const CLASSIC_START_Y: f32 = 250.0;
+const CLASSIC_STEP_Y: f32 = 35.0;   // Quanto cada level clássico começa mais baixo
+const CLASSIC_BARRIER_Y: f32 = -200.0;
+const CLASSIC_BARRIER_MARGIN: f32 = 60.0;
+const CLASSIC_BARRIER_COUNT: i32 = 4;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    start_y: f32,                  // Altura da primeira linha de inimigos
+    formation: Vec<String>,        // Linhas da formação, de cima para baixo
+    #[serde(default)]
+    barriers: Vec<[f32; 2]>,       // Posição (x, y) de cada barreira
+    enemy_speed: Option<f32>,      // Sobrescreve o enemy_speed do tuning
+    enemy_shot_time: Option<f32>,  // Sobrescreve o enemy_shot_time do tuning
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    level: Vec<Level>,
+}
+
+fn is_enemy_cell(cell: char) -> bool { // '1' até o número de tipos de inimigo
+    return match cell.to_digit(10) {
+        Some(enemy_type) => enemy_type >= 1 && enemy_type as usize <= cfg::ENEMY_TYPES,
+        None => false,
+    };
+}
+
+impl Level {
+    fn classic(screen_width: u32, diff_level: i32, tuning: &cfg::Tuning) -> Self { // Grade retangular do jogo original
+        let mut formation = Vec::new();
+
+        for j in 0..tuning.enemy_nline {
+            // Faixas do arcade: em cada 5 linhas, a de cima é do tipo '3', as duas seguintes do '2' e o resto do '1'
+            let enemy_type = match j * 5 / tuning.enemy_nline {
+                0 => '3',
+                1 | 2 => '2',
+                _ => '1',
+            };
+            formation.push((0..tuning.enemy_ncolumn).map(|_| enemy_type).collect());
+        }
+
+        let spacing = ((screen_width as f32) - CLASSIC_BARRIER_MARGIN*2.0)/(CLASSIC_BARRIER_COUNT as f32);
+        let mut x_pos = CLASSIC_BARRIER_MARGIN - (screen_width as f32/2.0) + spacing/2.0;
+        let mut barriers = Vec::new();
+
+        for _i in 0..CLASSIC_BARRIER_COUNT {
+            barriers.push([x_pos, CLASSIC_BARRIER_Y]);
+            x_pos += spacing;
+        }
+
+        Level {
+            start_y: CLASSIC_START_Y - (diff_level as f32) * CLASSIC_STEP_Y,
+            formation,
+            barriers,
+            enemy_speed: None,
+            enemy_shot_time: None,
+        }
+    }
+
+    fn validate(&self, index: usize) -> Result<(), String> {
+        let columns = match self.formation.first() {
+            Some(row) => row.chars().count(),
+            None => return Err(format!("Level {}: formação vazia", index + 1)),
+        };
+
+        for row in &self.formation {
+            if row.chars().count() != columns {
+                return Err(format!("Level {}: todas as linhas da formação precisam ter o mesmo tamanho", index + 1));
+            }
+            if let Some(c) = row.chars().find(|c| *c != '.' && !is_enemy_cell(*c)) {
+                return Err(format!("Level {}: caractere '{}' inválido na formação", index + 1, c));
+            }
+        }
+
+        if !self.formation.iter().any(|row| row.chars().any(|c| c != '.')) {
+            return Err(format!("Level {}: a formação precisa de pelo menos um inimigo", index + 1));
+        }
+
+        for value in self.enemy_speed.iter().chain(self.enemy_shot_time.iter()) {
+            if !(*value > 0.0) {
+                return Err(format!("Level {}: velocidade e cadência de tiro precisam ser maiores que zero", index + 1));
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn get_enemy_speed(&self, tuning: &cfg::Tuning) -> f32 {
+        return self.enemy_speed.unwrap_or(tuning.enemy_speed);
+    }
+
+    pub fn get_enemy_shot_time(&self, tuning: &cfg::Tuning) -> f32 {
+        return self.enemy_shot_time.unwrap_or(tuning.enemy_shot_time);
+    }
+
+    pub fn create_enemies(&self, screen_width: u32, tuning: &cfg::Tuning) -> Vec<ecs::Entity> { // Cria os inimigos nas suas posições corretas
+        let mut vec = Vec::new();
+
+        let columns = self.formation[0].chars().count();
+        let spacing = ((screen_width as f32) - FORMATION_MARGIN*2.0)/(columns as f32); // Espaço entre inimigos
+        let initial_x_pos = FORMATION_MARGIN - (screen_width as f32/2.0) + spacing/2.0;
+        let mut y_pos = self.start_y;
+
+        for row in &self.formation {
+            let mut x_pos = initial_x_pos;
+            for cell in row.chars() {
+                if is_enemy_cell(cell) {
+                    let enemy_type = cell.to_digit(10).unwrap_or(1) as i32;
+                    vec.push(go::new_enemy(Point2::new(x_pos, y_pos), enemy_type, tuning));
+                }
+                x_pos += spacing;
+            }
+            y_pos -= spacing;
+        }
+        return vec;
+    }
+
+    pub fn create_barriers(&self, tuning: &cfg::Tuning) -> Vec<ecs::Entity> { // Cria as barreiras nas suas posições corretas
+        return self.barriers.iter()
+            .map(|pos| go::new_barrier(Point2::new(pos[0], pos[1]), tuning))
+            .collect();
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct LevelPack {
+    levels: Vec<Level>, // Nunca vazia
+}
+
+impl LevelPack {
+    pub fn classic(screen_width: u32, tuning: &cfg::Tuning) -> Self { // Levels do jogo original, cada um começando mais baixo
+        let levels = (0..tuning.max_diff_level + 1)
+            .map(|diff_level| Level::classic(screen_width, diff_level, tuning))
+            .collect();
+        LevelPack { levels }
+    }
+
+    pub fn parse(text: &str) -> Result<LevelPack, String> {
+        let file: LevelFile = toml::from_str(text).map_err(|e| format!("Pacote de levels inválido: {}", e))?;
+        if file.level.is_empty() {
+            return Err(String::from("O pacote de levels precisa de pelo menos um level"));
+        }
+        for (i, level) in file.level.iter().enumerate() {
+            level.validate(i)?;
+        }
+        return Ok(LevelPack { levels: file.level });
+    }
+
+    pub fn load(path: &Path) -> Result<LevelPack, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Não foi possível ler {}: {}", path.display(), e))?;
+        return LevelPack::parse(&text).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    pub fn get_level(&self, level: i32) -> &Level { // Depois do último level, repete o último
+        let index = (level.max(0) as usize).min(self.levels.len() - 1);
+        return &self.levels[index];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row_points(enemies: &[ecs::Entity]) -> Vec<i32> { // Pontos de cada linha, de cima para baixo
+        let mut rows: Vec<(f32, i32)> = Vec::new();
+        for enemy in enemies {
+            let points = match enemy.reward {
+                Some(ecs::Reward::Points(points)) => points,
+                _ => panic!("inimigo sem pontos"),
+            };
+            let y = enemy.get_pos().y;
+            if !rows.iter().any(|row| row.0 == y) {
+                rows.push((y, points));
+            }
+        }
+        return rows.iter().map(|row| row.1).collect();
+    }
+
+    #[test]
+    fn classic_rows_follow_the_arcade_bands() {
+        let tuning = cfg::Tuning::default();
+        let enemies = LevelPack::classic(480, &tuning).get_level(0).create_enemies(480, &tuning);
+        assert_eq!(row_points(&enemies), vec![30, 20, 20, 10, 10]);
+    }
+
+    #[test]
+    fn formation_accepts_every_enemy_type() {
+        let tuning = cfg::Tuning::default();
+        let pack = LevelPack::parse("[[level]]\nstart_y = 250.0\nformation = [\"333\", \"2.2\", \"111\"]\n").unwrap();
+        assert_eq!(row_points(&pack.get_level(0).create_enemies(480, &tuning)), vec![30, 20, 10]);
+
+        assert!(LevelPack::parse("[[level]]\nstart_y = 250.0\nformation = [\"141\"]\n").is_err());
+    }
+}
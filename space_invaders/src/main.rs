@@ -3,23 +3,120 @@
 
 extern crate ggez;
 extern crate rand;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
 
 use ggez::conf;
 use ggez::{ContextBuilder};
 use ggez::event;
 use std::env;
 use std::path;
+use std::process;
 
 
-mod asse; // carrega os modulos com as funções e classes
+mod anm; // carrega os modulos com as funções e classes
+mod asse;
+mod bch;
+mod cfg;
+mod cli;
+mod col;
+mod ecs;
+mod evt;
 mod ms;
 mod go;
+mod hs;
+mod inp;
+mod lvl;
+mod msk;
+mod mtc;
+mod rpl;
+mod sim;
+mod sys;
+mod vpt;
+
+struct Game { // O que a partida precisa, já com os arquivos carregados
+    seed: u64,
+    replay_mode: rpl::ReplayMode,
+    tuning: cfg::Tuning,
+    levels: lvl::LevelPack,
+}
+
+fn load_game(options: &cli::Options) -> Result<Game, String> {
+    let replay_mode = match (&options.record, &options.replay) {
+        (Some(path), _) => rpl::ReplayMode::Record(path.clone()),
+        (None, Some(path)) => rpl::ReplayMode::Play(rpl::Replay::load(path)?),
+        (None, None) => rpl::ReplayMode::Off,
+    };
+
+    // Sem --config usa o tuning.toml da pasta atual, se existir
+    let tuning = match options.config {
+        Some(ref path) => cfg::Tuning::load(path)?,
+        None if path::Path::new(cfg::DEFAULT_CONFIG_FILE).exists() => cfg::Tuning::load(path::Path::new(cfg::DEFAULT_CONFIG_FILE))?,
+        None => cfg::Tuning::default(),
+    };
+    let tuning = tuning.with_difficulty(options.difficulty);
+
+    // Sem --levels usa a grade clássica, descendo a cada level
+    let levels = match options.levels {
+        Some(ref path) => lvl::LevelPack::load(path)?,
+        None => lvl::LevelPack::classic(vpt::WIDTH, &tuning),
+    };
+    if let rpl::ReplayMode::Play(ref replay) = replay_mode {
+        replay.check_setup(&tuning, &levels)?;
+    }
+
+    return Ok(Game {
+        seed: options.seed.unwrap_or_else(sim::random_seed),
+        replay_mode,
+        tuning,
+        levels,
+    });
+}
 
 fn main(){
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(cli::Command::Play(options)) => options,
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(cli::Command::BenchCollisions) => { // Só mede a fase ampla das colisões, sem abrir o jogo
+            bch::run();
+            return;
+        }
+        Err(e) => {
+            println!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    let game = match load_game(&options) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    if options.headless { // Sem janela só faz sentido verificar um replay
+        if let rpl::ReplayMode::Play(replay) = game.replay_mode {
+            if rpl::simulate(replay, vpt::WIDTH, vpt::HEIGHT, game.tuning, game.levels) {
+                println!("Replay verificado com sucesso");
+            } else {
+                println!("Replay divergiu do checksum gravado");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let fullscreen_type = if options.fullscreen { conf::FullscreenType::Desktop } else { conf::FullscreenType::Off };
     let mut cb = ContextBuilder::new("space_invaders", "ggez")
-        .window_setup(conf::WindowSetup::default().title("Best Space Invaders Ever"))
-        .window_mode(conf::WindowMode::default().dimensions(480, 640));
+        .window_setup(conf::WindowSetup::default().title("Best Space Invaders Ever").resizable(true))
+        .window_mode(conf::WindowMode::default().dimensions(options.window.0, options.window.1).fullscreen_type(fullscreen_type));
 
     if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
         let mut path = path::PathBuf::from(manifest_dir);
@@ -29,7 +126,7 @@ fn main(){
 
     let ctx = &mut cb.build().unwrap();
 
-    match ms::MainState::new(ctx) {
+    match ms::MainState::new(ctx, game.seed, game.replay_mode, game.tuning, game.levels, &options) {
         Err(e) => {
             println!("Could not load game!");
             println!("Error: {}", e);
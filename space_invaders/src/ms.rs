@@ -1,153 +1,121 @@
 extern crate ggez;
-extern crate rand;
 
 
 use ggez::{Context, GameResult};
-use ggez::graphics::{Point2, Vector2};
+use ggez::conf;
+use ggez::graphics::Point2;
 use ggez::graphics;
 use ggez::timer;
-use ggez::event::{EventHandler, Keycode, Mod};
-use rand::Rng;
+use ggez::event::{Axis, Button, EventHandler, Keycode, Mod};
 
 use asse;
-use go;
-use go::Movement;
-
-const PLAYER_SHOT_TIME: f32 = 0.5;
-const ENEMY_SHOT_TIME: f32 = 1.0;
-const ENEMY_NLINE: i32 = 5;
-const ENEMY_NCOLUMN: i32 = 11;
-const GAME_BOUNDS: f32 = 30.0;
-const MAX_DIFF_LEVEL: i32 = 7;
-const ENEMY_SPEED: f32 = 600.0;
-
-fn enemy_pos_calculator(enemy: &mut go::GameObj, // Atualiza a posição de um inimigo
-    player: &mut go::GameObj, 
-    reached_corner: bool, 
-    screen_bounds_x: f32, 
-    screen_bounds_y: f32) -> bool{
-
-    let mut reached_corner_1 = reached_corner; // Se um inimigo encostar no limite horizontal da tela
-
-    if enemy.get_pos_x() > screen_bounds_x{ 
-        reached_corner_1 = true;
-    }
-    else if enemy.get_pos_x() < -screen_bounds_x{
-        reached_corner_1 = true;  
-    }
-
-    if enemy.get_pos_y() < -screen_bounds_y{ // Se um inimigo sair da tela por baixo, o jogo acaba
-        player.set_hit_points(0);
-    }
-
-    return reached_corner_1;
-}
-
-
-fn create_enemies(screen_width: u32, diff_level: i32) -> Vec<go::GameObj> { // Cria os inimigos nas suas posições corretas
-    let mut vec = Vec::new();
-
-    let spacing = ((screen_width as f32) - 40.0*2.0)/(ENEMY_NCOLUMN as f32); // Espaço entre inimigos
-    let initial_x_pos = 40.0 - (screen_width as f32/2.0) + spacing/2.0; 
-    let mut x_pos;
-    let mut y_pos = 250.0 - (diff_level as f32) * 35.0;
-    let mut enemy_type = 1;
-
-
-    for _j in 0..ENEMY_NLINE {
-        x_pos = initial_x_pos;
-        for _i in 0..ENEMY_NCOLUMN {
-            let mut enemy = go::GameObj::new_enemy(Point2::new(x_pos, y_pos));
-            enemy.set_curr_sprite(enemy_type); // Define qual sprite de inimigo utilizar
-            vec.push(enemy);
-            x_pos += spacing; 
-        }
-        if enemy_type == 1{ // Cada coluna de inimigo possui uma sprite diferente
-            enemy_type = 2;
-        }
-        else {
-            enemy_type = 1;
-        }
-        y_pos -= spacing;
-    }
-    return vec;
-}
-
-fn create_barriers(screen_width: u32) -> Vec<go::GameObj> { // Cria as barreiras nas suas posições corretas
-    let mut vec = Vec::new();
-
-    let spacing = ((screen_width as f32) - 60.0*2.0)/4.0;
-    let mut x_pos = 60.0 - (screen_width as f32/2.0)  + spacing/2.0;
-    let y_pos = -200.0; // Posição da barreira é hardcoded 
-
-
-    for _i in 0..4 {
-        let mut enemy = go::GameObj::new_barrier(Point2::new(x_pos, y_pos));
-        vec.push(enemy);
-        x_pos += spacing; 
-    }
- 
-    return vec;
-}
-
-
-
-
-fn check_player_bounds(player: &mut go::GameObj, sx: f32) {
+use cfg;
+use cli;
+use ecs;
+use evt;
+use hs;
+use inp;
+use lvl;
+use msk;
+use mtc;
+use rpl;
+use sim;
+use vpt;
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::PathBuf;
+
+type BarrierImages = HashMap<ecs::EntityId, graphics::Image>; // Textura de cada barreira, refeita só quando ela muda
+
+fn draw_entity( // Desenha a sprite de uma entidade
+    assets: &asse::Assets,
+    ctx: &mut Context,
+    id: ecs::EntityId,
+    entity: &ecs::Entity,
+    barrier_images: &mut BarrierImages,
+    world_coords: (u32, u32),
+) -> GameResult<()> {
 
-    let screen_bounds = sx / 2.0 - GAME_BOUNDS;
-    
-    if player.get_pos_x() > screen_bounds {
-        player.set_pos_x(screen_bounds);
-    } else if player.get_pos_x() < -screen_bounds {
-        player.set_pos_x(-screen_bounds);
+    let sprite = match entity.sprite {
+        Some(ref sprite) => sprite,
+        None => return Ok(()),
+    };
+    if sprite.clip == ecs::Clip::Mask {
+        return draw_barrier(ctx, id, entity, barrier_images, world_coords);
     }
-}
 
-fn check_shot_bounds(shot: &mut go::GameObj, sy: f32) { // Não deixa o jogador sair da tela
-
-    let screen_bounds = sy / 2.0;
-    
-    if shot.get_pos_y() > screen_bounds {
-        shot.set_hit_points(0);
-    } else if shot.get_pos_y() < -screen_bounds {
-        shot.set_hit_points(0);
-    }
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, entity.get_pos());
+    // O segundo canhão do cooperativo é pintado de outra cor para os jogadores se acharem
+    let color = match entity.player_control {
+        Some(ref control) if control.slot > 0 => Some(graphics::Color::from_rgb(120, 200, 255)),
+        _ => None,
+    };
+    draw_clip(assets, ctx, sprite.clip, sprite.time, pos, color)
 }
 
-#[derive(Debug)]
-struct InputState { // Classe que contem o estado dos inputs do usuário
-    xaxis: f32,
-    is_firing: bool,
-}
+fn draw_clip( // Desenha o quadro atual de um clip centralizado em dest
+    assets: &asse::Assets,
+    ctx: &mut Context,
+    clip: ecs::Clip,
+    time: f32,
+    dest: Point2,
+    color: Option<graphics::Color>,
+) -> GameResult<()> {
 
-impl Default for InputState {
-    fn default() -> Self {
-        InputState {
-            xaxis: 0.0,
-            is_firing: false,
-        }
-    }
+    let (image, src) = match assets.clip_frame(clip, time) {
+        Some(frame) => frame,
+        None => return Ok(()),
+    };
+    let drawparams = graphics::DrawParam {
+        src,
+        dest,
+        rotation: 0.0,
+        offset: graphics::Point2::new(0.5, 0.5),
+        color,
+        ..Default::default()
+    };
+    graphics::draw_ex(ctx, image, drawparams)
 }
 
-
-fn draw_game_obj( // Desenha os sprites dos objetos
-    assets: &mut asse::Assets,
+fn draw_barrier( // Desenha a barreira a partir da máscara de dano
     ctx: &mut Context,
-    game_obj: &go::GameObj,
+    id: ecs::EntityId,
+    barrier: &ecs::Entity,
+    barrier_images: &mut BarrierImages,
     world_coords: (u32, u32),
 ) -> GameResult<()> {
 
+    let mask = match barrier.collider.as_ref().and_then(|collider| collider.mask.as_ref()) {
+        Some(mask) => mask,
+        None => return Ok(()),
+    };
+    if let Entry::Vacant(entry) = barrier_images.entry(id) {
+        entry.insert(barrier_image(ctx, mask)?);
+    }
+
     let (screen_w, screen_h) = world_coords;
-    let pos = world_to_screen_coords(screen_w, screen_h, game_obj.get_pos());
-    let image = assets.game_obj_sprite(game_obj);
     let drawparams = graphics::DrawParam {
-        dest: pos,
-        rotation: 0.0,
+        dest: world_to_screen_coords(screen_w, screen_h, barrier.get_pos()),
         offset: graphics::Point2::new(0.5, 0.5),
         ..Default::default()
     };
-    graphics::draw_ex(ctx, image, drawparams)
+    graphics::draw_ex(ctx, &barrier_images[&id], drawparams)
+}
+
+fn barrier_image(ctx: &mut Context, mask: &msk::DamageMask) -> GameResult<graphics::Image> { // Monta a textura da máscara pixel a pixel
+    let mut rgba = Vec::with_capacity(mask.get_width() * mask.get_height() * 4);
+    for y in 0..mask.get_height() {
+        for x in 0..mask.get_width() {
+            let alpha = if mask.is_solid(x as i32, y as i32) { 255 } else { 0 };
+            rgba.extend_from_slice(&[255, 255, 255, alpha]);
+        }
+    }
+
+    let mut image = graphics::Image::from_rgba8(ctx, mask.get_width() as u16, mask.get_height() as u16, &rgba)?;
+    image.set_filter(graphics::FilterMode::Nearest);
+    Ok(image)
 }
 
 // Passa as coordenadas do "mundo" para a tela
@@ -159,252 +127,711 @@ fn world_to_screen_coords(screen_width: u32, screen_height: u32, point: Point2)
     Point2::new(x, y)
 }
 
+fn draw_centered_text(ctx: &mut Context, text: &graphics::Text, screen_width: u32, y: f32) -> GameResult<()> { // Desenha um texto centralizado na horizontal
+    let x = (screen_width as f32 - text.width() as f32) / 2.0;
+    graphics::draw(ctx, text, Point2::new(x, y), 0.0)
+}
+
+fn input_names(bindings: &inp::Bindings, action: inp::Action) -> String { // Teclas da ação para os textos de ajuda, ou o botão se ela não tiver tecla
+    let inputs = bindings.get(action);
+    let keys: Vec<String> = inputs.iter().filter(|input| !input.is_pad()).map(|input| input.get_name()).collect();
+    if !keys.is_empty() {
+        return keys.join(" ou ");
+    }
+    return inputs.first().map_or(String::from("-"), |input| input.get_name());
+}
+
+const POPUP_TIME: f32 = 0.8;   // Tempo que a pontuação flutuante fica na tela
+const POPUP_SPEED: f32 = 30.0; // Velocidade com que ela sobe
+const TURN_BANNER_TIME: f32 = 1.5; // Tempo que o aviso de troca de jogador fica na tela
+const OVERLAY_SPACING: f32 = 30.0; // Distância entre as linhas dos textos do meio da tela
+
+struct Effect { // Animação que toca uma vez por cima da partida, como a explosão de um inimigo
+    clip: ecs::Clip,
+    pos: Point2,
+    time: f32,
+    slot: Option<usize>, // Canhão que fica escondido enquanto a animação toca
+}
 
-pub struct MainState { // Classe do estado atual do jogo
-    player: go::GameObj,
-    enemies: Vec<go::GameObj>,
-    barriers: Vec<go::GameObj>,
-    shots_player: Vec<go::GameObj>,
-    shots_enemy: Vec<go::GameObj>,
-    level: i32,
-    score: i32,
+struct FloatingScore { // Pontuação desenhada onde o inimigo morreu
+    text: graphics::Text,
+    pos: Point2,
+    timer: f32,
+}
+
+// Estados possíveis do jogo, cada um com seu update, draw e teclas
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GameState {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+    HighScoreEntry, // Jogador digitando as iniciais de um novo recorde
+    HighScores,     // Tabela de recordes aberta a partir do título
+    Controls,       // Troca das teclas e botões de cada ação
+}
+
+const PAUSE_ITEMS: [&str; 3] = ["Continuar", "Recomeçar", "Sair"]; // Opções do menu de pausa
+
+
+pub struct MainState { // Adaptador entre a simulação e o ggez (desenho, áudio e teclado)
+    game: mtc::Match,
     assets: asse::Assets,
     screen_width: u32,
     screen_height: u32,
-    input: InputState,
-    player_shot_timeout: f32, // Tempo de espera entre tiros do player
-    enemy_shot_timeout: f32,  // Tempo de espera entre tiros dos inimigos
-    enemy_sprite_timer: f32,
-    gui_dirty: bool,          // Flag de atualização da GUI
-    score_display: graphics::Text,
+    input: sim::InputState,
+    recording: Option<(rpl::Replay, PathBuf)>, // Replay sendo gravado e onde salvar
+    playback: Option<rpl::Playback>,           // Replay sendo reproduzido
+    score_displays: Vec<graphics::Text>,   // Um placar por jogador
     level_display: graphics::Text,
     hp_display: graphics::Text,
+    state: GameState,
+    pause_selected: usize,                 // Opção marcada no menu de pausa
+    overlay_display: Vec<graphics::Text>,  // Textos da tela atual (título, pausa, fim de jogo)
+    high_scores: hs::HighScoreTable,
+    initials: [u8; hs::INITIALS_LEN],      // Iniciais sendo digitadas na tela de recorde
+    initials_cursor: usize,
+    ufo_sound_playing: bool,
+    floating_scores: Vec<FloatingScore>,
+    effects: Vec<Effect>,
+    barrier_images: BarrierImages,
+    turn_banner: Option<(graphics::Text, f32)>, // Aviso de qual jogador joga agora, e por quanto tempo
+    entry_player: usize,                   // Jogador digitando as iniciais na tela de recorde
+    bindings: inp::Bindings,
+    axes: inp::AxisTracker,
+    held: inp::HeldActions,
+    controls_selected: usize,              // Ação marcada na tela de controles
+    rebinding: bool,                       // Esperando a nova tecla ou botão da ação marcada
+    rebind_refused: bool,                  // A última troca foi recusada por deixar outra ação sem entrada
+    viewport: vpt::Viewport,               // Escala da área de jogo lógica para a janela
+    muted: bool,
+    fixed_seed: Option<u64>,               // Seed do --seed, repetida em toda partida nova
 }
 
 
 impl MainState {
-    pub fn new(ctx: &mut Context) -> GameResult<MainState> {
-        graphics::set_background_color(ctx, (0, 0, 0, 255).into());
-
-        println!();
-        println!("Bem vindo ao melhor Space Invaders da existência");
-        println!();
-        println!("Como jogar:");
-        println!("Setinhas para esquerda e direita, espaço para atirar, esc para sair, boa sorte");
-        println!();
+    pub fn new(ctx: &mut Context, seed: u64, replay_mode: rpl::ReplayMode, tuning: cfg::Tuning, levels: lvl::LevelPack, options: &cli::Options) -> GameResult<MainState> {
+        graphics::set_default_filter(ctx, graphics::FilterMode::Nearest); // Sprites em pixel art continuam nítidas quando escaladas
 
-        let assets = asse::Assets::new(ctx)?;
+        let assets = asse::Assets::new(ctx, options.theme.as_deref())?;
+        graphics::set_background_color(ctx, assets.get_background());
+        graphics::set_color(ctx, assets.get_foreground())?;
         let score_disp = graphics::Text::new(ctx, "score", &assets.get_font())?;
         let level_disp = graphics::Text::new(ctx, "level", &assets.get_font())?;
         let hp_disp = graphics::Text::new(ctx, "hp", &assets.get_font())?;
 
-        // Criação dos objetos do jogo
+        let (seed, recording, playback) = match replay_mode {
+            rpl::ReplayMode::Off => (seed, None, None),
+            rpl::ReplayMode::Record(path) => (seed, Some((rpl::Replay::new(seed), path)), None),
+            rpl::ReplayMode::Play(replay) => (replay.get_seed(), None, Some(rpl::Playback::new(replay))),
+        };
+
+        // O replay diz o modo e o level da partida, fora dele o modo é escolhido na tela de título
+        let (mode, start_level) = match playback {
+            Some(ref playback) => (playback.get_replay().get_mode(), playback.get_replay().get_start_level()),
+            None => (mtc::Mode::Single, options.level),
+        };
+        let game = mtc::Match::new(mode, vpt::WIDTH, vpt::HEIGHT, seed, start_level, tuning, levels);
 
-        let player = go::GameObj::new_player();
+        println!("Seed da partida: {} (use --seed {} para repetir)", seed, seed);
 
-        let enemies = create_enemies(ctx.conf.window_mode.width, 0);
-        let barriers = create_barriers(ctx.conf.window_mode.width);
+        // O replay começa direto na partida, sem passar pela tela de título
+        let state = if playback.is_some() { GameState::Playing } else { GameState::Title };
 
-        let s = MainState {
-            player,
-            enemies,
-            barriers,
-            shots_player: Vec::new(),
-            shots_enemy: Vec::new(),
-            level: 0,
-            score: 0,
+        let (window_width, window_height) = graphics::get_size(ctx);
+        let mut s = MainState {
+            game,
             assets,
-            screen_width: ctx.conf.window_mode.width,
-            screen_height: ctx.conf.window_mode.height,
-            input: InputState::default(),
-            player_shot_timeout: 0.0,
-            enemy_shot_timeout: 0.0,
-            enemy_sprite_timer: 0.0,
-            gui_dirty: true,
-            score_display: score_disp,
+            screen_width: vpt::WIDTH,
+            screen_height: vpt::HEIGHT,
+            input: sim::InputState::default(),
+            recording,
+            playback,
+            score_displays: vec![score_disp],
             level_display: level_disp,
             hp_display: hp_disp,
+            state,
+            pause_selected: 0,
+            overlay_display: Vec::new(),
+            high_scores: hs::load(ctx),
+            initials: [b'A'; hs::INITIALS_LEN],
+            initials_cursor: 0,
+            ufo_sound_playing: false,
+            floating_scores: Vec::new(),
+            effects: Vec::new(),
+            barrier_images: HashMap::new(),
+            turn_banner: None,
+            entry_player: 0,
+            bindings: inp::load(ctx),
+            axes: inp::AxisTracker::default(),
+            held: inp::HeldActions::default(),
+            controls_selected: 0,
+            rebinding: false,
+            rebind_refused: false,
+            viewport: vpt::Viewport::new(window_width, window_height, ctx.conf.window_mode.fullscreen_type != conf::FullscreenType::Off),
+            muted: options.mute,
+            fixed_seed: options.seed,
         };
+        s.viewport.apply(ctx)?;
+        s.update_ui(ctx);
+        s.update_overlay(ctx)?;
 
         Ok(s)
     }
 
-    fn activate_enemy_shot(&mut self) { // Função que dispara um tiro do inimigo
-        self.enemy_shot_timeout = ENEMY_SHOT_TIME;
+    fn finish_recording(&mut self) { // Salva o replay gravado, se houver um
+        if let Some((mut replay, path)) = self.recording.take() {
+            replay.finish(&self.game);
+            match replay.save(&path) {
+                Ok(()) => println!("Replay salvo em {}", path.display()),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
 
-        // Escolhe um inimigo aleatório
-        let enemy_shooter = (rand::thread_rng().gen_range(0, self.enemies.len() as i32)) as usize;
+    fn finish_playback(&mut self) -> bool { // Confere o replay quando ele chega ao fim
+        let finished = match self.playback {
+            Some(ref playback) => playback.is_finished(self.game.get_frame()),
+            None => false,
+        };
 
-        let shot = go::GameObj::new_shot(self.enemies[enemy_shooter].get_pos()+Vector2::new(0.0, -20.0),Vector2::new(0.0, -1.0));
+        if finished {
+            let playback = self.playback.take().unwrap();
+            if playback.verify(&self.game) {
+                println!("Replay reproduzido com sucesso, checksum {}", playback.get_replay().get_checksum());
+            }
+            else {
+                println!("Replay divergiu! Esperado {}, obtido {}",
+                    playback.get_replay().get_checksum(),
+                    rpl::match_checksum(&self.game));
+            }
+        }
+        return finished;
+    }
 
-        self.shots_enemy.push(shot);
+    fn play_sounds(&mut self, events: &[evt::Event]) { // Toca o som de cada evento da simulação que tiver um
+        if self.muted {
+            return;
+        }
+        for event in events {
+            let _ = match *event {
+                evt::Event::ShotFired { faction: ecs::Faction::Player } => self.assets.get_shot_sound().play(),
+                evt::Event::PlayerHit { .. } => self.assets.get_player_hit_sound().play(),
+                evt::Event::EnemyKilled { .. } => self.assets.get_enemy_hit_sound().play(),
+                _ => Ok(()),
+            };
+        }
     }
 
-    fn activate_player_shot(&mut self) { // Função que dispara um tiro do player
-        self.player_shot_timeout = PLAYER_SHOT_TIME;
+    fn update_floating_scores(&mut self, ctx: &mut Context, events: &[evt::Event], seconds: f32) -> GameResult<()> { // Cria, move e remove as pontuações flutuantes
+        for event in events {
+            if let evt::Event::EnemyKilled { pos, points } = *event {
+                let text = graphics::Text::new(ctx, &points.to_string(), &self.assets.get_font())?;
+                self.floating_scores.push(FloatingScore { text, pos, timer: POPUP_TIME });
+            }
+        }
 
-        let player = &self.player;
-        let shot = go::GameObj::new_shot(player.get_pos()+Vector2::new(0.0, 20.0), Vector2::new(0.0, 1.0));
+        for floating in &mut self.floating_scores {
+            floating.pos.y += POPUP_SPEED * seconds;
+            floating.timer -= seconds;
+        }
+        self.floating_scores.retain(|floating| floating.timer > 0.0);
+        Ok(())
+    }
 
-        self.shots_player.push(shot);
-        let _ = self.assets.get_shot_sound().play();
+    fn update_effects(&mut self, events: &[evt::Event], seconds: f32) { // Cria, anima e remove as explosões
+        for effect in &mut self.effects {
+            effect.time += seconds;
+        }
+        // Mesmo um clip marcado com loop toca uma vez só como efeito
+        let assets = &self.assets;
+        self.effects.retain(|effect| assets.get_animation(effect.clip).map_or(false, |animation| effect.time < animation.get_length()));
+
+        for event in events {
+            match *event {
+                evt::Event::EnemyKilled { pos, .. } => self.effects.push(Effect { clip: ecs::Clip::Explosion, pos, time: 0.0, slot: None }),
+                evt::Event::PlayerHit { slot, pos, .. } => self.effects.push(Effect { clip: ecs::Clip::PlayerDeath, pos, time: 0.0, slot: Some(slot) }),
+                _ => {}
+            }
+        }
     }
 
-    fn remove_objects(&mut self) { // Remove do jogo os objetos que estão com o HP zerado
+    fn update_barrier_images(&mut self, events: &[evt::Event]) { // Joga fora as texturas das barreiras que mudaram
+        // Com outro level ou o tabuleiro do outro jogador os ids passam a ser de outras barreiras
+        let changed = events.iter().any(|event| matches!(*event,
+            evt::Event::BarrierDamaged { .. } | evt::Event::LevelCleared { .. } | evt::Event::TurnChanged { .. }));
+        if changed {
+            self.barrier_images.clear();
+        }
+    }
 
-        let mut index_list = Vec::new(); // Lista de elementos a serem removidos
+    fn update_ufo_sound(&mut self) { // O som do disco voador só toca enquanto ele estiver cruzando a tela na partida
+        let should_play = !self.muted && self.state == GameState::Playing && self.game.get_board().has_ufo();
+        let sound = self.assets.get_ufo_sound();
 
-        for i in 0..( self.shots_player.len() as i32){
-            if self.shots_player[i as usize].get_hit_points() == 0
-            {
-                index_list.push(i);
+        if should_play && !self.ufo_sound_playing {
+            // O loop é colocado na fila uma vez só, depois é apenas pausado e retomado
+            if sound.stopped() {
+                let _ = sound.play();
             }
+            sound.resume();
         }
+        else if !should_play && self.ufo_sound_playing {
+            sound.pause();
+        }
+        self.ufo_sound_playing = should_play;
+    }
+
+    fn set_state(&mut self, ctx: &mut Context, state: GameState) -> GameResult<()> { // Troca de estado e remonta a tela
+        self.state = state;
+        self.update_ufo_sound();
+        self.update_overlay(ctx)
+    }
 
-        for _i in 0..( index_list.len() as i32){ // Remove um elemento por vez de acordo com a lista
-            let index_pop = index_list.pop().unwrap() as usize;
-            drop(&self.shots_player[index_pop]);
-            self.shots_player.remove(index_pop); 
+    fn new_match(&mut self, ctx: &mut Context, mode: mtc::Mode, seed: u64) { // Troca a partida por uma nova, do zero
+        let tuning = self.game.get_board().get_tuning().clone();
+        let levels = self.game.get_board().get_levels().clone();
+        let start_level = self.game.get_start_level();
+        self.game = mtc::Match::new(mode, self.screen_width, self.screen_height, seed, start_level, tuning, levels);
+        self.input = sim::InputState::default();
+        self.floating_scores.clear();
+        self.effects.clear();
+        self.barrier_images.clear();
+        self.turn_banner = None;
+        self.update_ui(ctx);
+    }
+
+    fn start(&mut self, ctx: &mut Context, mode: mtc::Mode) -> GameResult<()> { // Sai da tela de título no modo escolhido
+        if mode != self.game.get_mode() { // Mantém a seed escolhida, que pode estar sendo gravada
+            let seed = self.game.get_seed();
+            self.new_match(ctx, mode, seed);
         }
+        self.set_state(ctx, GameState::Playing)
+    }
 
-        for i in 0..(self.shots_enemy.len() as i32){
-            if self.shots_enemy[i as usize].get_hit_points() == 0
-            {
-                index_list.push(i);
+    fn restart(&mut self, ctx: &mut Context) -> GameResult<()> { // Começa uma partida nova sem fechar o jogo
+        self.finish_recording();
+        let mode = self.game.get_mode();
+        let seed = self.fixed_seed.unwrap_or_else(sim::random_seed);
+        println!("Seed da partida: {} (use --seed {} para repetir)", seed, seed);
+        self.new_match(ctx, mode, seed);
+        self.set_state(ctx, GameState::Playing)
+    }
+
+    fn end_game(&mut self, ctx: &mut Context) -> GameResult<()> { // Vai para a tela de recorde ou direto para o fim de jogo
+        self.finish_recording();
+        self.entry_player = 0;
+        self.next_high_score_entry(ctx)
+    }
+
+    fn next_high_score_entry(&mut self, ctx: &mut Context) -> GameResult<()> { // Pede as iniciais do próximo jogador que fez um recorde
+        // Partidas reproduzidas de um replay não entram na tabela
+        if self.playback.is_none() {
+            while self.entry_player < self.game.get_boards().len() {
+                if self.high_scores.qualifies(self.game.get_boards()[self.entry_player].get_score()) {
+                    self.initials = [b'A'; hs::INITIALS_LEN];
+                    self.initials_cursor = 0;
+                    return self.set_state(ctx, GameState::HighScoreEntry);
+                }
+                self.entry_player += 1;
             }
         }
+        self.set_state(ctx, GameState::GameOver)
+    }
 
-        for _i in 0..( index_list.len() as i32){
-            let index_pop = index_list.pop().unwrap() as usize;
-            drop(&self.shots_enemy[index_pop]);
-            self.shots_enemy.remove(index_pop);
-        }
+    fn confirm_initials(&mut self, ctx: &mut Context) -> GameResult<()> { // Grava o novo recorde e passa para o próximo jogador
+        let initials = String::from_utf8_lossy(&self.initials).to_string();
+        let entry = {
+            let board = &self.game.get_boards()[self.entry_player];
+            hs::HighScore::new(&initials, board.get_score(), board.get_level(), &hs::today())
+        };
+        self.high_scores.insert(entry);
+        hs::save(ctx, &self.high_scores);
+        self.entry_player += 1;
+        self.next_high_score_entry(ctx)
+    }
+
+    fn change_initial(&mut self, delta: i32) { // Troca a letra sob o cursor, dando a volta no alfabeto
+        let letter = (self.initials[self.initials_cursor] - b'A') as i32;
+        self.initials[self.initials_cursor] = b'A' + (letter + delta).rem_euclid(26) as u8;
+    }
 
+    fn handle_input(&mut self, ctx: &mut Context, input: inp::Input, pad: Option<usize>, pressed: bool) { // Entrada do teclado ou de um controle
+        let actions = self.bindings.actions(input, pad);
 
-        for i in 0..(self.enemies.len() as i32){
-            if self.enemies[i as usize].get_hit_points() == 0
-            {
-                index_list.push(i);
+        if self.playback.is_some() { // Durante o replay o input vem do arquivo, só dá para sair
+            if pressed && actions.contains(&inp::Action::Back) {
+                let _ = ctx.quit();
             }
+            return;
         }
 
-        for _i in 0..( index_list.len() as i32){
-            let index_pop = index_list.pop().unwrap() as usize;
-            drop(&self.enemies[index_pop]);
-            self.enemies.remove(index_pop);
+        if pressed && self.rebinding { // A entrada vira a nova ligação da ação marcada
+            self.rebind_refused = !self.bindings.bind(inp::ACTIONS[self.controls_selected], input);
+            self.rebinding = false;
+            if let Err(e) = self.update_overlay(ctx) {
+                println!("Erro ao tratar tecla: {}", e);
+            }
+            return;
         }
 
-        for i in 0..(self.barriers.len() as i32){
-            if self.barriers[i as usize].get_hit_points() == 0
-            {
-                index_list.push(i);
-            }
+        if !pressed {
+            self.held.release(input, pad);
+            return;
+        }
+        for &action in &actions { // Direções e tiro ficam seguradas em qualquer estado e só viram input na partida
+            self.held.press(action, input, pad);
         }
 
-        for _i in 0..( index_list.len() as i32){
-            let index_pop = index_list.pop().unwrap() as usize;
-            drop(&self.barriers[index_pop]);
-            self.barriers.remove(index_pop);
+        let state = self.state;
+        for action in actions {
+            // Uma entrada pode ter várias ações (Esc pausa e volta), só a primeira que o estado usa vale
+            match self.press(ctx, action) {
+                Ok(true) => break,
+                Ok(false) => (),
+                Err(e) => {
+                    println!("Erro ao tratar tecla: {}", e);
+                    break;
+                }
+            }
+        }
+        // O botão A confirma e atira: quando a entrada troca de tela ela não fica segurada na partida
+        if self.state != state {
+            self.held.release(input, pad);
         }
     }
 
-    fn collisions(&mut self) { // Função que gerencia as colisões
-        
-        
-        for shot_player in &mut self.shots_player {
-            for enemy in &mut self.enemies {
-                let distance = enemy.get_pos() - shot_player.get_pos(); // Tiro do player com inimigo
-                if distance.norm() < (shot_player.get_size() + enemy.get_size()) {
-                    shot_player.set_hit_points(0);
-                    enemy.set_hit_points(0);
-                    self.score += 1;
-                    self.gui_dirty = true;
-                    let _ = self.assets.get_enemy_hit_sound().play();
-                }
+    fn press(&mut self, ctx: &mut Context, action: inp::Action) -> GameResult<bool> { // Devolve se o estado atual usou a ação
+        match (self.state, action) {
+            (_, inp::Action::Fullscreen) => self.viewport.toggle_fullscreen(ctx)?,
+            (_, inp::Action::PixelPerfect) => { // Liga e desliga a escala inteira
+                let integer_scale = !self.viewport.is_integer_scale();
+                self.viewport.set_integer_scale(integer_scale);
+                self.viewport.apply(ctx)?
             }
 
-            for barrier in &mut self.barriers {
-                let distance = barrier.get_pos() - shot_player.get_pos(); // Tiro do player com a barreira
-                if distance.norm() < (shot_player.get_size() + barrier.get_size()) {
-                    shot_player.set_hit_points(0);
-                    barrier.sub_hit_points(); 
-                    let b_curr_sprite = barrier.get_curr_sprite();
-                    barrier.set_curr_sprite(b_curr_sprite + 1); // Atualiza o sprite da barreira para um mais "destruído"
-                }
+            (GameState::Title, inp::Action::Confirm) => self.start(ctx, mtc::Mode::Single)?,
+            (GameState::Title, inp::Action::Back) => ctx.quit()?,
+            (GameState::Title, inp::Action::TwoPlayers) => self.start(ctx, mtc::Mode::Alternating)?,
+            (GameState::Title, inp::Action::Coop) => self.start(ctx, mtc::Mode::Coop(sim::Lives::PerCannon))?,
+            (GameState::Title, inp::Action::CoopShared) => self.start(ctx, mtc::Mode::Coop(sim::Lives::Shared))?,
+            (GameState::Title, inp::Action::HighScores) => self.set_state(ctx, GameState::HighScores)?,
+            (GameState::Title, inp::Action::Controls) => {
+                self.controls_selected = 0;
+                self.rebind_refused = false;
+                self.set_state(ctx, GameState::Controls)?
             }
 
-            for shot_enemy in &mut self.shots_enemy{
-                let distance = shot_enemy.get_pos() - shot_player.get_pos(); // Tiro do player com tiro do inimigo
-                if distance.norm() < (shot_player.get_size() + shot_enemy.get_size()) {
-                    shot_player.set_hit_points(0);
-                    shot_enemy.set_hit_points(0);
-                }
+            (GameState::Playing, inp::Action::Left(_)) | (GameState::Playing, inp::Action::Right(_)) | (GameState::Playing, inp::Action::Fire(_)) => (), // Já ficaram seguradas em held
+            (GameState::Playing, inp::Action::Pause) => {
+                self.pause_selected = 0;
+                self.set_state(ctx, GameState::Paused)?
+            }
 
-            } 
-        }
+            (GameState::Paused, inp::Action::Up) => {
+                self.pause_selected = (self.pause_selected + PAUSE_ITEMS.len() - 1) % PAUSE_ITEMS.len();
+                self.update_overlay(ctx)?
+            }
+            (GameState::Paused, inp::Action::Down) => {
+                self.pause_selected = (self.pause_selected + 1) % PAUSE_ITEMS.len();
+                self.update_overlay(ctx)?
+            }
+            (GameState::Paused, inp::Action::Confirm) => match self.pause_selected {
+                0 => self.set_state(ctx, GameState::Playing)?,
+                1 => self.restart(ctx)?,
+                _ => ctx.quit()?,
+            },
+            (GameState::Paused, inp::Action::Pause) | (GameState::Paused, inp::Action::Back) => self.set_state(ctx, GameState::Playing)?,
+
+            (GameState::GameOver, inp::Action::Confirm) | (GameState::GameOver, inp::Action::Restart) => self.restart(ctx)?,
+            (GameState::GameOver, inp::Action::Back) => ctx.quit()?,
+
+            (GameState::HighScoreEntry, inp::Action::Up) => {
+                self.change_initial(1);
+                self.update_overlay(ctx)?
+            }
+            (GameState::HighScoreEntry, inp::Action::Down) => {
+                self.change_initial(-1);
+                self.update_overlay(ctx)?
+            }
+            (GameState::HighScoreEntry, inp::Action::Left(_)) => {
+                self.initials_cursor = self.initials_cursor.saturating_sub(1);
+                self.update_overlay(ctx)?
+            }
+            (GameState::HighScoreEntry, inp::Action::Right(_)) => {
+                self.initials_cursor = (self.initials_cursor + 1).min(hs::INITIALS_LEN - 1);
+                self.update_overlay(ctx)?
+            }
+            (GameState::HighScoreEntry, inp::Action::Confirm) => self.confirm_initials(ctx)?,
 
-        for shot_enemy in &mut self.shots_enemy {
-            let distance = shot_enemy.get_pos() - self.player.get_pos(); // Tiro do inimigo com o player
-            if distance.norm() < (self.player.get_size() + shot_enemy.get_size()) {
-                self.player.sub_hit_points();
-                self.gui_dirty = true;
-                shot_enemy.set_hit_points(0);
-                let _ = self.assets.get_player_hit_sound().play();
+            (GameState::HighScores, inp::Action::Confirm) | (GameState::HighScores, inp::Action::Back) | (GameState::HighScores, inp::Action::HighScores) => {
+                self.set_state(ctx, GameState::Title)?
             }
 
-            for barrier in &mut self.barriers {
-                let distance = barrier.get_pos() - shot_enemy.get_pos(); // Tiro do inimigo com a barreira
-                if distance.norm() < (shot_enemy.get_size() + barrier.get_size()) {
-                    shot_enemy.set_hit_points(0);
-                    barrier.sub_hit_points();
-                    let b_curr_sprite = barrier.get_curr_sprite();
-                    barrier.set_curr_sprite(b_curr_sprite + 1);
-                }
+            (GameState::Controls, inp::Action::Up) => {
+                self.controls_selected = (self.controls_selected + inp::ACTIONS.len() - 1) % inp::ACTIONS.len();
+                self.update_overlay(ctx)?
             }
+            (GameState::Controls, inp::Action::Down) => {
+                self.controls_selected = (self.controls_selected + 1) % inp::ACTIONS.len();
+                self.update_overlay(ctx)?
+            }
+            (GameState::Controls, inp::Action::Confirm) => {
+                self.rebinding = true;
+                self.rebind_refused = false;
+                self.update_overlay(ctx)?
+            }
+            (GameState::Controls, inp::Action::ResetControls) => {
+                self.bindings = inp::Bindings::default();
+                self.rebind_refused = false;
+                self.update_overlay(ctx)?
+            }
+            (GameState::Controls, inp::Action::Back) => {
+                inp::save(ctx, &self.bindings);
+                self.set_state(ctx, GameState::Title)?
+            }
+
+            _ => return Ok(false),
         }
+        Ok(true)
+    }
 
+    fn update_overlay(&mut self, ctx: &mut Context) -> GameResult<()> { // Monta os textos desenhados por cima do jogo
+        let names = |action| input_names(&self.bindings, action);
+        let lines = match self.state {
+            GameState::Title => vec![
+                String::from("BEST SPACE INVADERS EVER"),
+                format!("{} e {} para mover", names(inp::Action::Left(0)), names(inp::Action::Right(0))),
+                format!("{} para atirar", names(inp::Action::Fire(0))),
+                format!("No cooperativo: {}, {} e {}", names(inp::Action::Left(1)), names(inp::Action::Right(1)), names(inp::Action::Fire(1))),
+                format!("{} para pausar", names(inp::Action::Pause)),
+                format!("Aperte {} para começar", names(inp::Action::Confirm)),
+                format!("{} para dois jogadores alternados", names(inp::Action::TwoPlayers)),
+                format!("{} cooperativo, {} com vidas juntas", names(inp::Action::Coop), names(inp::Action::CoopShared)),
+                format!("{} para ver os recordes", names(inp::Action::HighScores)),
+                format!("{} para trocar os controles", names(inp::Action::Controls)),
+            ],
+            GameState::Playing => Vec::new(),
+            GameState::Paused => {
+                let mut lines = vec![String::from("PAUSADO")];
+                for (i, item) in PAUSE_ITEMS.iter().enumerate() {
+                    if i == self.pause_selected {
+                        lines.push(format!("> {} <", item));
+                    }
+                    else {
+                        lines.push(item.to_string());
+                    }
+                }
+                lines
+            },
+            GameState::GameOver => {
+                let mut lines = vec![String::from("GAME OVER")];
+                if self.game.get_boards().len() == 1 {
+                    lines.push(format!("Score: {}", self.game.get_board().get_score()));
+                    lines.push(format!("Level: {}", self.game.get_board().get_level()));
+                }
+                else {
+                    for (i, board) in self.game.get_boards().iter().enumerate() {
+                        lines.push(format!("Jogador {}: {} (Level {})", i + 1, board.get_score(), board.get_level()));
+                    }
+                }
+                lines.push(format!("Aperte {} ou {} para jogar de novo", names(inp::Action::Restart), names(inp::Action::Confirm)));
+                lines.push(format!("{} para sair", names(inp::Action::Back)));
+                lines
+            },
+            GameState::HighScoreEntry => {
+                let mut initials = String::new();
+                for (i, letter) in self.initials.iter().enumerate() {
+                    if i == self.initials_cursor {
+                        initials.push_str(&format!("[{}]", *letter as char));
+                    }
+                    else {
+                        initials.push_str(&format!(" {} ", *letter as char));
+                    }
+                }
+                let title = if self.game.get_boards().len() == 1 {
+                    String::from("NOVO RECORDE!")
+                }
+                else {
+                    format!("NOVO RECORDE! JOGADOR {}", self.entry_player + 1)
+                };
+                vec![
+                    title,
+                    format!("Score: {}", self.game.get_boards()[self.entry_player].get_score()),
+                    initials,
+                    format!("{} e {} para escolher as iniciais", names(inp::Action::Up), names(inp::Action::Down)),
+                    format!("{} para confirmar", names(inp::Action::Confirm)),
+                ]
+            },
+            GameState::HighScores => {
+                let mut lines = vec![String::from("RECORDES")];
+                for (i, entry) in self.high_scores.get_entries().iter().enumerate() {
+                    lines.push(format!("{:2}. {} {:6} L{:<2} {}",
+                        i + 1,
+                        entry.get_initials(),
+                        entry.get_score(),
+                        entry.get_level(),
+                        entry.get_date()));
+                }
+                if self.high_scores.get_entries().is_empty() {
+                    lines.push(String::from("Nenhum recorde ainda"));
+                }
+                lines.push(format!("{} para voltar", names(inp::Action::Back)));
+                lines
+            },
+            GameState::Controls => {
+                let mut lines = vec![String::from("CONTROLES")];
+                for (i, action) in inp::ACTIONS.iter().enumerate() {
+                    // Mostra só a primeira tecla e o primeiro botão de cada ação
+                    let inputs = self.bindings.get(*action);
+                    let key = inputs.iter().find(|input| !input.is_pad()).map_or(String::from("-"), |input| input.get_name());
+                    let pad = inputs.iter().find(|input| input.is_pad()).map_or(String::from("-"), |input| input.get_name());
+                    let line = format!("{}: {} {}", action.get_label(), key, pad);
+                    if i == self.controls_selected {
+                        lines.push(format!("> {} <", line));
+                    }
+                    else {
+                        lines.push(line);
+                    }
+                }
+                if self.rebinding {
+                    lines.push(String::from("Aperte a tecla ou botão novo"));
+                    lines.push(String::from("Esc cancela"));
+                }
+                else {
+                    if self.rebind_refused {
+                        lines.push(String::from("Essa troca deixaria outra ação sem entrada"));
+                    }
+                    lines.push(format!("{} troca, {} restaura", names(inp::Action::Confirm), names(inp::Action::ResetControls)));
+                    lines.push(format!("{} salva e volta", names(inp::Action::Back)));
+                }
+                lines
+            },
+        };
 
-        for enemy in &mut self.enemies {
-            let distance = enemy.get_pos() - self.player.get_pos(); // Inimigo com o player
-            if distance.norm() < (self.player.get_size() + enemy.get_size()) {
-                self.player.set_hit_points(0);
-            }
+        self.overlay_display.clear();
+        for line in lines.iter() {
+            let text = graphics::Text::new(ctx, line, &self.assets.get_font())?;
+            self.overlay_display.push(text);
+        }
+        Ok(())
+    }
 
-            for barrier in &mut self.barriers {
-                let distance = barrier.get_pos() - enemy.get_pos(); // Inimigo com a barreira
-                if distance.norm() < (enemy.get_size() + barrier.get_size()) {
-                    barrier.set_hit_points(0); // Simplesmente destrói a barreira
+    fn draw_world(&mut self, ctx: &mut Context) -> GameResult<()> { // Desenha os objetos e a interface da partida
+        {
+            let assets = &self.assets;
+            let coords = (self.screen_width, self.screen_height);
+            let effects = &self.effects;
+            let dying = |entity: &ecs::Entity| match entity.player_control {
+                Some(ref control) => effects.iter().any(|effect| effect.slot == Some(control.slot)),
+                None => false,
+            };
+
+            let entities = self.game.get_board().get_entities();
+            for id in entities.ids() {
+                match entities.get(id) {
+                    Some(entity) if !dying(entity) => draw_entity(assets, ctx, id, entity, &mut self.barrier_images, coords)?,
+                    _ => (),
                 }
             }
+            for effect in effects {
+                let pos = world_to_screen_coords(coords.0, coords.1, effect.pos);
+                draw_clip(assets, ctx, effect.clip, effect.time, pos, None)?;
+            }
+        }
+
+        for floating in &self.floating_scores {
+            let pos = world_to_screen_coords(self.screen_width, self.screen_height, floating.pos);
+            let drawparams = graphics::DrawParam {
+                dest: pos,
+                offset: Point2::new(0.5, 0.5),
+                ..Default::default()
+            };
+            graphics::draw_ex(ctx, &floating.text, drawparams)?;
+        }
+
+        // Desenha a user interface
+
+        let width = self.screen_width as f32; // Posições em proporção da largura lógica
+        let level_dest = graphics::Point2::new(10.0, 10.0);
+        let hp_dest = graphics::Point2::new(width * 0.75, 10.0);
+        graphics::draw(ctx, &self.level_display, level_dest, 0.0)?;
+        for (i, score_display) in self.score_displays.iter().enumerate() { // Placares um embaixo do outro
+            let score_dest = graphics::Point2::new(width * 0.375, 10.0 + 25.0 * (i as f32));
+            graphics::draw(ctx, score_display, score_dest, 0.0)?;
+        }
+        graphics::draw(ctx, &self.hp_display, hp_dest, 0.0)?;
+
+        if let Some((ref text, _)) = self.turn_banner {
+            draw_centered_text(ctx, text, self.screen_width, (self.screen_height as f32) / 2.0)?;
         }
+        Ok(())
+    }
 
+    fn draw_overlay(&mut self, ctx: &mut Context) -> GameResult<()> { // Desenha os textos do estado atual no meio da tela
+        let lines = self.overlay_display.len() as f32;
+        let spacing = OVERLAY_SPACING.min(self.screen_height as f32 / (lines + 1.0)); // Listas compridas, como a dos controles, ficam mais juntas
+        let mut y = (self.screen_height as f32) / 2.0 - spacing / 2.0 * lines;
+        for text in &self.overlay_display {
+            draw_centered_text(ctx, text, self.screen_width, y)?;
+            y += spacing;
+        }
+        Ok(())
     }
 
-    fn check_for_level_respawn(&mut self) { // Recarrega os inimigos e as barreiras caso o level passe
-        if self.enemies.is_empty() {
-            self.level += 1;
-            let mut diff_level = self.level;
-            self.gui_dirty = true;
-            if self.level > MAX_DIFF_LEVEL
-            {
-                diff_level = MAX_DIFF_LEVEL;  
+    fn draw_letterbox(&mut self, ctx: &mut Context) -> GameResult<()> { // Cobre o que passou para fora da área de jogo, como o disco voador entrando
+        graphics::set_color(ctx, self.assets.get_background())?;
+        for bar in self.viewport.get_bars() {
+            graphics::rectangle(ctx, graphics::DrawMode::Fill, bar)?;
+        }
+        graphics::set_color(ctx, self.assets.get_foreground())
+    }
+
+    fn update_turn_banner(&mut self, ctx: &mut Context, events: &[evt::Event], seconds: f32) -> GameResult<()> { // Avisa de quem é a vez quando ela troca
+        for event in events {
+            if let evt::Event::TurnChanged { player } = *event {
+                let text = graphics::Text::new(ctx, &format!("JOGADOR {}", player + 1), &self.assets.get_font())?;
+                self.turn_banner = Some((text, TURN_BANNER_TIME));
+                self.floating_scores.clear(); // As pontuações e explosões eram do tabuleiro do outro jogador
+                self.effects.clear();
             }
-            let new_enemies = create_enemies(self.screen_width, diff_level);
-            let new_barriers = create_barriers(self.screen_width);
-            self.enemies.extend(new_enemies);
-            self.barriers = new_barriers;
         }
+
+        let expired = match self.turn_banner {
+            Some((_, ref mut timer)) => {
+                *timer -= seconds;
+                *timer <= 0.0
+            }
+            None => false,
+        };
+        if expired {
+            self.turn_banner = None;
+        }
+        Ok(())
     }
 
     fn update_ui(&mut self, ctx: &mut Context) { // Faz o update da user interface
-        let score_str = format!("Score: {}", self.score);
-        let level_str = format!("Level: {}", self.level);
-        let hp_str = format!("Lives: {}", self.player.get_hit_points());
-        let score_text = graphics::Text::new(ctx, &score_str, &self.assets.get_font()).unwrap();
+        let board = self.game.get_board(); // Level e vidas são do jogador da vez
+        let level_str = format!("Level: {}", board.get_level());
+        let hp_str = match self.game.get_mode() {
+            mtc::Mode::Coop(sim::Lives::PerCannon) => format!("Lives: {}/{}", board.get_lives(0), board.get_lives(1)), // Vidas de cada canhão
+            _ => format!("Lives: {}", board.get_lives(0)),
+        };
         let level_text = graphics::Text::new(ctx, &level_str, &self.assets.get_font()).unwrap();
         let hp_text = graphics::Text::new(ctx, &hp_str, &self.assets.get_font()).unwrap();
 
-        self.score_display = score_text;
+        self.score_displays.clear();
+        for (i, board) in self.game.get_boards().iter().enumerate() {
+            let score_str = if self.game.get_boards().len() == 1 {
+                format!("Score: {:05}", board.get_score()) // Placar com zeros à esquerda, como no arcade
+            }
+            else {
+                let marker = if i == self.game.get_current() { ">" } else { " " };
+                format!("{}P{}: {:05}", marker, i + 1, board.get_score())
+            };
+            self.score_displays.push(graphics::Text::new(ctx, &score_str, &self.assets.get_font()).unwrap());
+        }
         self.level_display = level_text;
         self.hp_display = hp_text;
     }
@@ -414,102 +841,44 @@ impl MainState {
 
 impl EventHandler for MainState { // Loop principal do jogo, onde tudo é atualizado
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        const DESIRED_FPS: u32 = 30;
+        const DESIRED_FPS: u32 = rpl::REPLAY_FPS;
 
         while timer::check_update_time(ctx, DESIRED_FPS) {
             let seconds = 1.0 / (DESIRED_FPS as f32);
 
-            self.enemy_sprite_timer -= seconds;
-            self.player.set_direction(Vector2::new(self.input.xaxis, 0.0));
-            self.player_shot_timeout -= seconds;
-            if self.input.is_firing && self.player_shot_timeout < 0.0 {
-                self.activate_player_shot();
-            }
-            self.enemy_shot_timeout -= seconds;
-            if self.enemy_shot_timeout < 0.0 {
-                self.activate_enemy_shot();
-            }
-
-            self.player.update_position(seconds);
-            check_player_bounds(&mut self.player, self.screen_width as f32);
-           
-            for mut shot_player in &mut self.shots_player {
-                shot_player.update_position(seconds);
-                check_shot_bounds(&mut shot_player, self.screen_height as f32);
-            }
-
-
-            for mut shot_enemy in &mut self.shots_enemy {
-                shot_enemy.update_position(seconds);
-                check_shot_bounds(&mut shot_enemy, self.screen_height as f32);
-            }
-
-            let screen_bounds_x = (self.screen_width as f32) / 2.0 - GAME_BOUNDS;
-            let screen_bounds_y = (self.screen_height as f32) / 2.0 - GAME_BOUNDS;
-            let mut reached_corner = false;
-
-            // Velocidade dos inimigos aumenta inversamente proporcional ao número de inimigos
-            let enemy_speed = ENEMY_SPEED/(self.enemies.len() as f32);
-
-            for mut enemy in &mut self.enemies{
-
-                if self.enemy_sprite_timer < 0.0 {
-                    let e_curr_sprite = enemy.get_curr_sprite();
-                    enemy.set_curr_sprite(e_curr_sprite * -1);
-                }
-
-                enemy.set_speed(enemy_speed);
-                reached_corner = enemy_pos_calculator(enemy, 
-                                                    &mut self.player, 
-                                                    reached_corner, 
-                                                    screen_bounds_x, 
-                                                    screen_bounds_y);
-            }
-
-            if self.enemy_sprite_timer < 0.0 { // Troca os sprites mais rapido quando os inimigos são mais rapidos
-                self.enemy_sprite_timer = 10.0/enemy_speed;        
+            if self.state != GameState::Playing { // Fora da partida o passo fixo fica congelado
+                continue;
             }
 
-            if reached_corner{ // Move os inimigos pra baixo e troca sua direção
-                for mut enemy in &mut self.enemies{
-                    let e_pos_y = enemy.get_pos_y();
-                    enemy.set_pos_y(e_pos_y - 15.0);
-                    let e_direction_x = enemy.get_direction_x();
-                    enemy.set_direction(Vector2::new(-e_direction_x, 0.0));
-                }
-            }
-
-            for enemy in &mut self.enemies {
-                enemy.update_position(seconds);
+            self.input = match self.playback {
+                Some(ref mut playback) => playback.input_at(self.game.get_frame()).clone(),
+                None => self.held.to_input_state(), // Resolve as teclas seguradas a cada frame
+            };
+            if let Some((ref mut replay, _)) = self.recording {
+                replay.record(self.game.get_frame(), &self.input);
             }
 
-            self.collisions();
-            self.remove_objects();
-            self.check_for_level_respawn();
-            if self.gui_dirty {
+            self.game.step(seconds, &self.input);
+
+            // Cada parte da interface lê os eventos do passo por conta própria
+            let events = self.game.take_events();
+            self.play_sounds(&events);
+            self.update_ufo_sound();
+            self.update_floating_scores(ctx, &events, seconds)?;
+            self.update_effects(&events, seconds);
+            self.update_barrier_images(&events);
+            self.update_turn_banner(ctx, &events, seconds)?;
+            if events.iter().any(evt::Event::changes_hud) {
                 self.update_ui(ctx);
-                self.gui_dirty = false;
             }
 
-            if self.player.get_hit_points() <= 0 {
-                for mut enemy in &mut self.enemies{
-                    drop(enemy);
-                }
-                for mut shot in &mut self.shots_player{
-                    drop(shot);
-                }
-                for mut shot in &mut self.shots_enemy{
-                    drop(shot);
-                }
-                for mut barrier in &mut self.barriers{
-                    drop(barrier);
-                }
-
-                drop(&self.player);
-
-                println!("Você perdeu, que pena. Pontuação: {}", self.score);
+            if self.finish_playback() {
                 let _ = ctx.quit();
             }
+
+            if self.game.is_game_over() {
+                self.end_game(ctx)?;
+            }
         }
 
         Ok(())
@@ -517,73 +886,64 @@ impl EventHandler for MainState { // Loop principal do jogo, onde tudo é atuali
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> { // Função que desenha na tela tudo
         graphics::clear(ctx);
-        
-        { // Desenha os objetos
-            let assets = &mut self.assets;
-            let coords = (self.screen_width, self.screen_height);
-
-            let p = &self.player;
-            draw_game_obj(assets, ctx, p, coords)?;
-
-            for s in &self.shots_player {
-                draw_game_obj(assets, ctx, s, coords)?;
-            }
-
-            for s in &self.shots_enemy {
-                draw_game_obj(assets, ctx, s, coords)?;
-            }
-
-            for b in &self.barriers {
-                draw_game_obj(assets, ctx, b, coords)?;
-            }
 
-            for e in &self.enemies {
-                draw_game_obj(assets, ctx, e, coords)?;
+        match self.state {
+            GameState::Title | GameState::HighScores | GameState::Controls => self.draw_overlay(ctx)?,
+            GameState::Playing => self.draw_world(ctx)?,
+            GameState::Paused | GameState::GameOver | GameState::HighScoreEntry => {
+                self.draw_world(ctx)?;
+                self.draw_overlay(ctx)?;
             }
         }
 
-        // Desenha a user interface
-
-        let level_dest = graphics::Point2::new(10.0, 10.0);
-        let score_dest = graphics::Point2::new(180.0, 10.0);
-        let hp_dest = graphics::Point2::new(360.0, 10.0);
-        graphics::draw(ctx, &self.level_display, level_dest, 0.0)?;
-        graphics::draw(ctx, &self.score_display, score_dest, 0.0)?;
-        graphics::draw(ctx, &self.hp_display, hp_dest, 0.0)?;
-
+        self.draw_letterbox(ctx)?;
         graphics::present(ctx);
 
         timer::yield_now();
         Ok(())
     }
 
-    // Mapeia as teclas apertadas para a classe input
-    fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool) {
-        match keycode {
-            Keycode::Left => {
-                self.input.xaxis = -1.0;
-            }
-            Keycode::Right => {
-                self.input.xaxis = 1.0;
-            }
-            Keycode::Space => {
-                self.input.is_firing = true;
-            }
+    fn resize_event(&mut self, ctx: &mut Context, width: u32, height: u32) { // A área de jogo continua do mesmo tamanho lógico
+        self.viewport.resize(width, height);
+        if let Err(e) = self.viewport.apply(ctx) {
+            println!("Erro ao redimensionar a janela: {}", e);
+        }
+    }
 
-            Keycode::Escape => ctx.quit().unwrap(),
-            _ => (),
+    fn quit_event(&mut self, _ctx: &mut Context) -> bool { // Salva o replay mesmo se o jogador sair no meio da partida
+        self.finish_recording();
+        return false;
+    }
+
+    // Teclas e controles passam pelo mapeamento de inp antes de virarem ações
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, repeat: bool) {
+        if self.state == GameState::Controls && self.rebinding && keycode == Keycode::Escape { // Cancela a troca
+            self.rebinding = false;
+            let _ = self.update_overlay(ctx);
+            return;
         }
+        if repeat && self.rebinding { // A repetição da tecla que abriu a troca não conta
+            return;
+        }
+        self.handle_input(ctx, inp::Input::Key(keycode), None, true);
     }
 
-    fn key_up_event(&mut self, _ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool) {
-        match keycode {
-            Keycode::Left | Keycode::Right => {
-                self.input.xaxis = 0.0;
-            }
-            Keycode::Space => {
-                self.input.is_firing = false;
-            }
-            _ => (),
+    fn key_up_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool) {
+        self.handle_input(ctx, inp::Input::Key(keycode), None, false);
+    }
+
+    fn controller_button_down_event(&mut self, ctx: &mut Context, btn: Button, instance_id: i32) {
+        self.handle_input(ctx, inp::Input::Button(btn), Some(instance_id.max(0) as usize), true);
+    }
+
+    fn controller_button_up_event(&mut self, ctx: &mut Context, btn: Button, instance_id: i32) {
+        self.handle_input(ctx, inp::Input::Button(btn), Some(instance_id.max(0) as usize), false);
+    }
+
+    fn controller_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: i16, instance_id: i32) {
+        let pad = instance_id.max(0) as usize;
+        for (input, pressed) in self.axes.update(pad, axis, value) {
+            self.handle_input(ctx, input, Some(pad), pressed);
         }
     }
 }
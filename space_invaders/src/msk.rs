@@ -0,0 +1,112 @@
+// Máscara de dano das barreiras: uma grade de pixels, cada um intacto ou destruído.
+// Ela faz parte do estado da simulação, então cada tiro ou inimigo corrói
+// exatamente a área que toca e os tiros passam pelos buracos.
+//
+// Coordenadas locais: (0, 0) é o canto superior esquerdo, y cresce para baixo.
+
+const CORNER_CUT: usize = 2; // Pixels cortados em cada canto, como na sprite original
+
+#[derive(Debug, Clone)]
+pub struct DamageMask {
+    width: usize,
+    height: usize,
+    solid: Vec<bool>, // Linha a linha, de cima para baixo
+}
+
+impl DamageMask {
+    pub fn new_shield(width: usize, height: usize) -> Self { // Barreira inteira, com os cantos arredondados
+        let mut mask = DamageMask {
+            width,
+            height,
+            solid: vec![true; width * height],
+        };
+
+        for i in 0..CORNER_CUT.min(width).min(height) {
+            for j in 0..(CORNER_CUT - i).min(width) {
+                mask.clear(j, i);
+                mask.clear(width - 1 - j, i);
+                mask.clear(j, height - 1 - i);
+                mask.clear(width - 1 - j, height - 1 - i);
+            }
+        }
+        return mask;
+    }
+
+    pub fn get_width(&self) -> usize {
+        return self.width;
+    }
+
+    pub fn get_height(&self) -> usize {
+        return self.height;
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32) -> bool { // Fora da máscara conta como vazio
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return false;
+        }
+        return self.solid[(y as usize) * self.width + (x as usize)];
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return !self.solid.iter().any(|s| *s);
+    }
+
+    fn clear(&mut self, x: usize, y: usize) {
+        self.solid[y * self.width + x] = false;
+    }
+
+    pub fn erode_circle(&mut self, cx: f32, cy: f32, radius: f32) -> usize { // Destrói os pixels dentro do círculo, retorna quantos
+        let mut removed = 0;
+        let x0 = (cx - radius).floor().max(0.0) as usize;
+        let y0 = (cy - radius).floor().max(0.0) as usize;
+        let x1 = ((cx + radius).ceil().max(0.0) as usize).min(self.width);
+        let y1 = ((cy + radius).ceil().max(0.0) as usize).min(self.height);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let dx = (x as f32) + 0.5 - cx; // Distância a partir do centro do pixel
+                let dy = (y as f32) + 0.5 - cy;
+                if dx * dx + dy * dy <= radius * radius && self.is_solid(x as i32, y as i32) {
+                    self.clear(x, y);
+                    removed += 1;
+                }
+            }
+        }
+        return removed;
+    }
+
+    pub fn erode_rect(&mut self, left: f32, top: f32, right: f32, bottom: f32) -> usize { // Destrói os pixels dentro do retângulo, retorna quantos
+        let mut removed = 0;
+        let x0 = left.floor().max(0.0) as usize;
+        let y0 = top.floor().max(0.0) as usize;
+        let x1 = (right.ceil().max(0.0) as usize).min(self.width);
+        let y1 = (bottom.ceil().max(0.0) as usize).min(self.height);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if self.is_solid(x as i32, y as i32) {
+                    self.clear(x, y);
+                    removed += 1;
+                }
+            }
+        }
+        return removed;
+    }
+
+    pub fn first_solid_in_column(&self, left: i32, right: i32, from_y: i32, to_y: i32) -> Option<(i32, i32)> { // Procura o primeiro pixel intacto indo de from_y até to_y
+        let step = if to_y >= from_y { 1 } else { -1 };
+        let mut y = from_y;
+
+        loop {
+            for x in left..(right + 1) {
+                if self.is_solid(x, y) {
+                    return Some((x, y));
+                }
+            }
+            if y == to_y {
+                return None;
+            }
+            y += step;
+        }
+    }
+}
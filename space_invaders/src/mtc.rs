@@ -0,0 +1,146 @@
+// Partida com um ou mais jogadores. No modo alternado cada jogador tem o seu
+// próprio tabuleiro (sim::World) com placar, vidas, level, inimigos e barreiras, e
+// eles se alternam como no arcade: quando um perde uma vida, a vez passa para o
+// outro, que continua o próprio tabuleiro de onde parou. No cooperativo os dois
+// canhões dividem o mesmo tabuleiro ao mesmo tempo.
+
+use cfg;
+use evt;
+use lvl;
+use sim;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Single,
+    Alternating,      // Dois jogadores, um de cada vez
+    Coop(sim::Lives), // Dois canhões na mesma tela
+}
+
+impl Mode {
+    pub fn get_name(&self) -> &'static str { // Nome usado no arquivo de replay
+        return match *self {
+            Mode::Single => "single",
+            Mode::Alternating => "alternating",
+            Mode::Coop(sim::Lives::PerCannon) => "coop",
+            Mode::Coop(sim::Lives::Shared) => "coop-shared",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        return match name {
+            "single" => Some(Mode::Single),
+            "alternating" => Some(Mode::Alternating),
+            "coop" => Some(Mode::Coop(sim::Lives::PerCannon)),
+            "coop-shared" => Some(Mode::Coop(sim::Lives::Shared)),
+            _ => None,
+        };
+    }
+}
+
+
+pub struct Match {
+    mode: Mode,
+    boards: Vec<sim::World>, // Um tabuleiro por jogador
+    current: usize,          // Jogador da vez
+    seed: u64,
+    start_level: i32,        // Level em que todos os tabuleiros começam
+    frame: u64,              // Passos simulados na partida toda, somando todos os jogadores
+    events: Vec<evt::Event>, // Eventos do tabuleiro da vez e as trocas de vez
+}
+
+fn board_seed(seed: u64, player: usize) -> u64 { // Cada tabuleiro sorteia com a sua própria seed
+    return seed.wrapping_add(player as u64);
+}
+
+impl Match {
+    pub fn new(mode: Mode, screen_width: u32, screen_height: u32, seed: u64, start_level: i32, tuning: cfg::Tuning, levels: lvl::LevelPack) -> Match {
+        let (players, cannons, lives) = match mode {
+            Mode::Single => (1, 1, sim::Lives::PerCannon),
+            Mode::Alternating => (2, 1, sim::Lives::PerCannon),
+            Mode::Coop(lives) => (1, 2, lives),
+        };
+        let boards = (0..players)
+            .map(|player| {
+                let mut board = sim::World::new(cannons, lives, screen_width, screen_height, board_seed(seed, player), tuning.clone(), levels.clone());
+                if start_level > 0 {
+                    board.start_at_level(start_level);
+                }
+                board
+            })
+            .collect();
+
+        Match {
+            mode,
+            boards,
+            current: 0,
+            seed,
+            start_level,
+            frame: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn get_board(&self) -> &sim::World { // Tabuleiro do jogador da vez
+        return &self.boards[self.current];
+    }
+
+    pub fn get_boards(&self) -> &[sim::World] {
+        return &self.boards;
+    }
+
+    pub fn get_current(&self) -> usize {
+        return self.current;
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        return self.mode;
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn get_start_level(&self) -> i32 {
+        return self.start_level;
+    }
+
+    pub fn get_frame(&self) -> u64 {
+        return self.frame;
+    }
+
+    pub fn is_game_over(&self) -> bool { // A partida só acaba quando todos os jogadores perderem
+        return self.boards.iter().all(|board| board.is_game_over());
+    }
+
+    pub fn take_events(&mut self) -> Vec<evt::Event> {
+        return self.events.drain(..).collect();
+    }
+
+    fn next_turn(&mut self) { // Passa a vez para o próximo jogador que ainda tem vidas
+        let players = self.boards.len();
+        for offset in 1..=players {
+            let next = (self.current + offset) % players;
+            if !self.boards[next].is_game_over() {
+                if next != self.current {
+                    self.current = next;
+                    self.events.push(evt::Event::TurnChanged { player: next });
+                }
+                return;
+            }
+        }
+    }
+
+    pub fn step(&mut self, seconds: f32, input: &sim::InputState) { // Avança só o tabuleiro da vez
+        let board = &mut self.boards[self.current];
+        board.step(seconds, input);
+
+        let events = board.take_events();
+        let lost_life = board.is_game_over() || events.iter().any(|event| matches!(*event, evt::Event::PlayerHit { .. }));
+        self.events.extend(events);
+
+        if lost_life {
+            self.next_turn();
+        }
+        self.frame += 1;
+    }
+}
@@ -0,0 +1,336 @@
+// Gravação e reprodução de partidas. Como a simulação é determinística
+// (seed fixa e passo de tempo fixo), basta guardar a seed e as mudanças de input
+// de cada frame para reproduzir a partida inteira.
+//
+// Formato do arquivo (texto, uma entrada por linha):
+//   seed <seed>
+//   mode <modo>   (opcional, single quando não existe)
+//   level <level> (opcional, level em que a partida começou, 0 quando não existe)
+//   setup <hash>  (opcional, hash do balanceamento e do pacote de levels da gravação)
+//   input <frame> <xaxis> <is_firing> [<xaxis> <is_firing> do segundo canhão]
+//   end <frame> <checksum>
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use cfg;
+use lvl;
+use mtc;
+use sim;
+
+pub enum ReplayMode { // O que fazer com o input da partida
+    Off,
+    Record(PathBuf),
+    Play(Replay),
+}
+
+pub const REPLAY_FPS: u32 = 30; // O replay só é válido com o mesmo passo fixo do loop principal
+
+#[derive(Debug, Clone, PartialEq)]
+struct InputFrame { // Estado do input a partir de um frame
+    frame: u64,
+    input: sim::InputState,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    seed: u64,
+    mode: mtc::Mode,
+    start_level: i32,
+    setup: Option<u64>, // None nos replays gravados antes dessa linha existir
+    inputs: Vec<InputFrame>,
+    end_frame: u64,
+    checksum: u64,
+}
+
+pub fn checksum(frame: u64, score: i32, level: i32) -> u64 { // FNV-1a do estado final da partida
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let values = [frame, score as u64, level as u64];
+
+    for value in values.iter() {
+        for i in 0..8 {
+            hash ^= (value >> (i * 8)) & 0xff;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    return hash;
+}
+
+pub fn setup_hash(tuning: &cfg::Tuning, levels: &lvl::LevelPack) -> u64 { // FNV-1a do balanceamento (já com a dificuldade) e dos levels
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in format!("{:?} {:?}", tuning, levels).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    return hash;
+}
+
+pub fn match_checksum(game: &mtc::Match) -> u64 { // Com um jogador é igual ao checksum de antes dos tabuleiros
+    let mut hash = game.get_frame();
+    for board in game.get_boards() {
+        hash = checksum(hash, board.get_score(), board.get_level());
+    }
+    return hash;
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed,
+            mode: mtc::Mode::Single,
+            start_level: 0,
+            setup: None,
+            inputs: Vec::new(),
+            end_frame: 0,
+            checksum: 0,
+        }
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn get_mode(&self) -> mtc::Mode {
+        return self.mode;
+    }
+
+    pub fn get_start_level(&self) -> i32 {
+        return self.start_level;
+    }
+
+    pub fn get_checksum(&self) -> u64 {
+        return self.checksum;
+    }
+
+    pub fn check_setup(&self, tuning: &cfg::Tuning, levels: &lvl::LevelPack) -> Result<(), String> { // Com outro balanceamento o replay divergiria sem aviso
+        match self.setup {
+            Some(setup) if setup != setup_hash(tuning, levels) => Err(String::from(
+                "O replay foi gravado com outro balanceamento, dificuldade ou pacote de levels (use os mesmos --config, --difficulty e --levels da gravação)")),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, input: &sim::InputState) { // Só guarda o input quando ele muda
+        let changed = match self.inputs.last() {
+            Some(last) => last.input != *input,
+            None => true,
+        };
+
+        if changed {
+            self.inputs.push(InputFrame { frame, input: input.clone() });
+        }
+    }
+
+    pub fn finish(&mut self, game: &mtc::Match) { // Marca o fim da gravação com o checksum do estado final
+        self.mode = game.get_mode();
+        self.start_level = game.get_start_level();
+        self.setup = Some(setup_hash(game.get_board().get_tuning(), game.get_board().get_levels()));
+        self.end_frame = game.get_frame();
+        self.checksum = match_checksum(game);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| format!("Não foi possível criar {}: {}", path.display(), e))?;
+        let mut text = String::new();
+
+        text.push_str(&format!("seed {}\n", self.seed));
+        text.push_str(&format!("mode {}\n", self.mode.get_name()));
+        if self.start_level > 0 {
+            text.push_str(&format!("level {}\n", self.start_level));
+        }
+        if let Some(setup) = self.setup {
+            text.push_str(&format!("setup {}\n", setup));
+        }
+        for input_frame in &self.inputs {
+            text.push_str(&format!("input {}", input_frame.frame));
+            for cannon in input_frame.input.cannons.iter() {
+                text.push_str(&format!(" {} {}", cannon.xaxis, if cannon.is_firing { 1 } else { 0 }));
+            }
+            text.push('\n');
+        }
+        text.push_str(&format!("end {} {}\n", self.end_frame, self.checksum));
+
+        return file.write_all(text.as_bytes()).map_err(|e| format!("Erro ao salvar {}: {}", path.display(), e));
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let file = File::open(path).map_err(|e| format!("Não foi possível abrir {}: {}", path.display(), e))?;
+        let mut seed = None;
+        let mut mode = mtc::Mode::Single;
+        let mut start_level = 0;
+        let mut setup = None;
+        let mut end = None;
+        let mut inputs = Vec::new();
+
+        for (n, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("Erro ao ler {}: {}", path.display(), e))?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let bad_line = || format!("Linha {} inválida no replay: {}", n + 1, line);
+
+            match fields.as_slice() {
+                [] => (),
+                ["seed", value] => seed = Some(value.parse::<u64>().map_err(|_| bad_line())?),
+                ["mode", name] => mode = mtc::Mode::from_name(name).ok_or_else(bad_line)?,
+                ["level", value] => start_level = value.parse::<i32>().ok().filter(|level| *level >= 0).ok_or_else(bad_line)?,
+                ["setup", value] => setup = Some(value.parse::<u64>().map_err(|_| bad_line())?),
+                ["input", frame, values @ ..] if !values.is_empty() && values.len() % 2 == 0 && values.len() <= 2 * sim::MAX_CANNONS => {
+                    let mut input = sim::InputState::default();
+                    for (cannon, pair) in input.cannons.iter_mut().zip(values.chunks(2)) {
+                        cannon.xaxis = pair[0].parse::<f32>().map_err(|_| bad_line())?;
+                        cannon.is_firing = pair[1] == "1";
+                    }
+                    let frame = frame.parse::<u64>().map_err(|_| bad_line())?;
+                    inputs.push(InputFrame { frame, input });
+                }
+                ["end", frame, sum] => {
+                    let frame = frame.parse::<u64>().map_err(|_| bad_line())?;
+                    let sum = sum.parse::<u64>().map_err(|_| bad_line())?;
+                    end = Some((frame, sum));
+                }
+                _ => return Err(bad_line()),
+            }
+        }
+
+        let seed = seed.ok_or_else(|| String::from("Replay sem seed"))?;
+        let (end_frame, checksum) = end.ok_or_else(|| String::from("Replay sem linha de fim"))?;
+
+        Ok(Replay {
+            seed,
+            mode,
+            start_level,
+            setup,
+            inputs,
+            end_frame,
+            checksum,
+        })
+    }
+}
+
+
+pub struct Playback { // Devolve o input gravado para cada frame
+    replay: Replay,
+    next: usize,
+    input: sim::InputState,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            replay,
+            next: 0,
+            input: sim::InputState::default(),
+        }
+    }
+
+    pub fn get_replay(&self) -> &Replay {
+        return &self.replay;
+    }
+
+    pub fn input_at(&mut self, frame: u64) -> &sim::InputState { // Os frames precisam ser pedidos em ordem crescente
+        while self.next < self.replay.inputs.len() && self.replay.inputs[self.next].frame <= frame {
+            self.input = self.replay.inputs[self.next].input.clone();
+            self.next += 1;
+        }
+        return &self.input;
+    }
+
+    pub fn is_finished(&self, frame: u64) -> bool {
+        return frame >= self.replay.end_frame;
+    }
+
+    pub fn verify(&self, game: &mtc::Match) -> bool { // Confere se a partida reproduzida chegou no mesmo estado
+        return match_checksum(game) == self.replay.checksum;
+    }
+}
+
+
+pub fn simulate(replay: Replay, screen_width: u32, screen_height: u32, tuning: cfg::Tuning, levels: lvl::LevelPack) -> bool { // Roda o replay sem janela e verifica o checksum
+    let seconds = 1.0 / (REPLAY_FPS as f32);
+    let mut game = mtc::Match::new(replay.get_mode(), screen_width, screen_height, replay.get_seed(), replay.get_start_level(), tuning, levels);
+    let mut playback = Playback::new(replay);
+
+    while !playback.is_finished(game.get_frame()) {
+        let input = playback.input_at(game.get_frame()).clone();
+        game.step(seconds, &input);
+        game.take_events(); // Sem janela ninguém consome os eventos
+    }
+    return playback.verify(&game);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 480;
+    const HEIGHT: u32 = 640;
+
+    fn classic() -> (cfg::Tuning, lvl::LevelPack) {
+        let tuning = cfg::Tuning::default();
+        let levels = lvl::LevelPack::classic(WIDTH, &tuning);
+        return (tuning, levels);
+    }
+
+    fn scripted_input(frame: u64) -> sim::InputState { // Anda de um lado para o outro atirando, o segundo canhão ao contrário
+        let mut input = sim::InputState::default();
+        let xaxis = if frame % 90 < 45 { 1.0 } else { -1.0 };
+        input.cannons[0].xaxis = xaxis;
+        input.cannons[0].is_firing = frame % 20 < 10;
+        input.cannons[1].xaxis = -xaxis;
+        input.cannons[1].is_firing = frame % 30 < 5;
+        return input;
+    }
+
+    fn record(mode: mtc::Mode, seed: u64, frames: u64) -> Replay { // Joga a partida sem janela, gravando como o MainState
+        let (tuning, levels) = classic();
+        let mut game = mtc::Match::new(mode, WIDTH, HEIGHT, seed, 0, tuning, levels);
+        let mut replay = Replay::new(seed);
+
+        while game.get_frame() < frames && !game.is_game_over() {
+            let input = scripted_input(game.get_frame());
+            replay.record(game.get_frame(), &input);
+            game.step(1.0 / (REPLAY_FPS as f32), &input);
+            game.take_events();
+        }
+        replay.finish(&game);
+        return replay;
+    }
+
+    #[test]
+    fn saved_replay_loads_back_the_same() {
+        let replay = record(mtc::Mode::Coop(sim::Lives::Shared), 42, 600);
+        let path = std::env::temp_dir().join("space_invaders_rpl_roundtrip.replay");
+
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap(), replay);
+    }
+
+    #[test]
+    fn simulate_reaches_the_recorded_checksum() {
+        for &mode in [mtc::Mode::Single, mtc::Mode::Alternating, mtc::Mode::Coop(sim::Lives::PerCannon)].iter() {
+            let replay = record(mode, 7, 900);
+            let (tuning, levels) = classic();
+            assert!(simulate(replay.clone(), WIDTH, HEIGHT, tuning, levels), "{:?}", mode);
+
+            let mut tampered = replay;
+            tampered.checksum ^= 1;
+            let (tuning, levels) = classic();
+            assert!(!simulate(tampered, WIDTH, HEIGHT, tuning, levels));
+        }
+    }
+
+    #[test]
+    fn other_setup_is_rejected() {
+        let replay = record(mtc::Mode::Single, 3, 60);
+        let (tuning, levels) = classic();
+        assert!(replay.check_setup(&tuning, &levels).is_ok());
+
+        let hard = tuning.clone().with_difficulty(cfg::Difficulty::Hard);
+        assert!(replay.check_setup(&hard, &levels).is_err());
+        let other_levels = lvl::LevelPack::classic(WIDTH / 2, &tuning);
+        assert!(replay.check_setup(&tuning, &other_levels).is_err());
+    }
+}
@@ -0,0 +1,506 @@
+extern crate ggez;
+extern crate rand;
+
+// Simulação do jogo, sem nenhuma dependência de janela, áudio ou Context do ggez.
+// O MainState apenas chama o step e desenha o resultado.
+//
+// Todo objeto do jogo é uma entidade (ecs.rs) guardada no EntityStore. O step roda as regras
+// do jogo (formação, tiros, disco voador, levels) e os sistemas de sys.rs.
+
+use ggez::graphics::{Point2, Vector2};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use std::cmp::Ordering;
+
+use cfg;
+use ecs;
+use evt;
+use go;
+use lvl;
+use sys;
+
+const GAME_BOUNDS: f32 = 30.0;
+const CANNON_SPACING: f32 = 120.0; // Distância inicial entre os canhões no modo cooperativo
+
+pub const MAX_CANNONS: usize = 2;
+
+fn level_points(points: i32, level: i32, tuning: &cfg::Tuning) -> i32 { // Aplica o multiplicador de level aos pontos
+    let multiplier = 1.0 + (level as f32) * tuning.level_score_multiplier;
+    return ((points as f32) * multiplier).round() as i32;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CannonInput { // Comandos de um canhão
+    pub xaxis: f32,
+    pub is_firing: bool,
+}
+
+impl Default for CannonInput {
+    fn default() -> Self {
+        CannonInput {
+            xaxis: 0.0,
+            is_firing: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InputState { // Classe que contem o estado dos inputs do usuário, um para cada canhão
+    pub cannons: [CannonInput; MAX_CANNONS],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lives { // Como as vidas são contadas quando há mais de um canhão
+    PerCannon, // Cada canhão tem as suas, quem perde todas sai da partida
+    Shared,    // Um acerto em qualquer canhão tira uma vida de todos
+}
+
+struct Cannon { // Canhão de um jogador na tela
+    id: ecs::EntityId,  // Deixa de valer quando o canhão é destruído
+    shot_timeout: f32,  // Tempo de espera entre tiros deste canhão
+}
+
+fn cannon_x(slot: usize, count: usize) -> f32 { // Canhões lado a lado, centralizados na tela
+    return (slot as f32 - (count - 1) as f32 / 2.0) * CANNON_SPACING;
+}
+
+
+pub struct World { // Classe com todo o estado da simulação
+    entities: ecs::EntityStore, // Todos os objetos do jogo
+    cannons: Vec<Cannon>,       // Um por jogador na tela, na ordem dos slots do input
+    lives: Lives,
+    shared_lives: i32,          // Vidas de todos quando elas são compartilhadas
+    ufo: Option<ecs::EntityId>, // Disco voador mais recente, se ainda existir
+    level: i32,
+    score: i32,
+    screen_width: u32,
+    screen_height: u32,
+    enemy_shot_timeout: f32,  // Tempo de espera entre tiros dos inimigos
+    formation_size: usize,    // Inimigos no começo do level, para acelerar a animação conforme eles morrem
+    ufo_timer: f32,           // Tempo até o próximo disco voador
+    events: Vec<evt::Event>,  // Eventos gerados desde a última leitura
+    game_over_sent: bool,     // O GameOver só é avisado uma vez
+    rng: XorShiftRng,         // Toda decisão aleatória da simulação passa por aqui
+    tuning: cfg::Tuning,      // Valores de balanceamento da partida
+    levels: lvl::LevelPack,   // Formações de cada level, em ordem
+}
+
+
+fn rng_from_seed(seed: u64) -> XorShiftRng { // O XorShift não aceita uma seed toda zerada, então misturamos constantes
+    let low = seed as u32;
+    let high = (seed >> 32) as u32;
+    return XorShiftRng::from_seed([low, high, low ^ 0x9E37_79B9, high ^ 0x7F4A_7C15]);
+}
+
+pub fn random_seed() -> u64 { // Sorteia uma seed quando o usuário não passa nenhuma
+    return rand::thread_rng().gen::<u64>();
+}
+
+
+impl World {
+    pub fn new(count: usize, lives: Lives, screen_width: u32, screen_height: u32, seed: u64, tuning: cfg::Tuning, levels: lvl::LevelPack) -> World {
+
+        // Criação dos objetos do jogo
+
+        let mut entities = ecs::EntityStore::new();
+        let count = count.clamp(1, MAX_CANNONS); // Quantidade de canhões
+        let cannons = (0..count)
+            .map(|slot| Cannon {
+                id: entities.insert(go::new_player(cannon_x(slot, count), slot, &tuning)),
+                shot_timeout: 0.0,
+            })
+            .collect();
+        for entity in levels.get_level(0).create_enemies(screen_width, &tuning) {
+            entities.insert(entity);
+        }
+        for entity in levels.get_level(0).create_barriers(&tuning) {
+            entities.insert(entity);
+        }
+        let formation_size = entities.iter().filter(|entity| entity.formation.is_some()).count();
+
+        World {
+            entities,
+            cannons,
+            lives,
+            shared_lives: tuning.player_hp,
+            ufo: None,
+            level: 0,
+            score: 0,
+            screen_width,
+            screen_height,
+            enemy_shot_timeout: 0.0,
+            formation_size,
+            ufo_timer: tuning.ufo_spawn_time,
+            events: Vec::new(),
+            game_over_sent: false,
+            rng: rng_from_seed(seed),
+            tuning,
+            levels,
+        }
+    }
+
+    pub fn get_entities(&self) -> &ecs::EntityStore {
+        return &self.entities;
+    }
+
+    pub fn get_cannon(&self, slot: usize) -> Option<&ecs::Entity> { // None depois que o canhão é destruído
+        return self.cannons.get(slot).and_then(|cannon| self.entities.get(cannon.id));
+    }
+
+    pub fn get_lives(&self, slot: usize) -> i32 { // Vidas que o canhão ainda tem
+        return match self.lives {
+            Lives::Shared => self.shared_lives,
+            Lives::PerCannon => self.get_cannon(slot).map_or(0, |cannon| cannon.get_hit_points()),
+        };
+    }
+
+    pub fn has_ufo(&self) -> bool {
+        return match self.ufo {
+            Some(id) => self.entities.contains(id),
+            None => false,
+        };
+    }
+
+    pub fn get_level(&self) -> i32 {
+        return self.level;
+    }
+
+    pub fn get_score(&self) -> i32 {
+        return self.score;
+    }
+
+    pub fn get_tuning(&self) -> &cfg::Tuning {
+        return &self.tuning;
+    }
+
+    pub fn get_levels(&self) -> &lvl::LevelPack {
+        return &self.levels;
+    }
+
+    pub fn is_game_over(&self) -> bool { // Acaba quando não sobra nenhum canhão
+        return !(0..self.cannons.len()).filter_map(|slot| self.get_cannon(slot)).any(|cannon| cannon.is_alive());
+    }
+
+    pub fn take_events(&mut self) -> Vec<evt::Event> { // Entrega os eventos pendentes, na ordem em que aconteceram
+        return self.events.drain(..).collect();
+    }
+
+    fn formation_count(&self) -> usize {
+        return self.entities.iter().filter(|entity| entity.formation.is_some()).count();
+    }
+
+    fn activate_enemy_shot(&mut self) { // Função que dispara um tiro do inimigo
+        self.enemy_shot_timeout = self.levels.get_level(self.level).get_enemy_shot_time(&self.tuning);
+
+        let targets: Vec<Point2> = (0..self.cannons.len())
+            .filter_map(|slot| self.get_cannon(slot))
+            .map(|cannon| cannon.get_pos())
+            .collect();
+
+        let shooter = if targets.len() > 1 {
+            // Com mais de um canhão sorteia o alvo e atira com o inimigo mais à frente na direção dele
+            let target = targets[self.rng.gen_range(0, targets.len())];
+            self.entities.iter()
+                .filter(|entity| entity.formation.is_some())
+                .map(|enemy| enemy.get_pos())
+                .min_by(|a, b| {
+                    let key_a = ((a.x - target.x).abs(), a.y);
+                    let key_b = ((b.x - target.x).abs(), b.y);
+                    key_a.partial_cmp(&key_b).unwrap_or(Ordering::Equal)
+                })
+        }
+        else {
+            // Escolhe um inimigo aleatório
+            let enemy_shooter = (self.rng.gen_range(0, self.formation_count() as i32)) as usize;
+            self.entities.iter().filter(|entity| entity.formation.is_some()).nth(enemy_shooter).map(|enemy| enemy.get_pos())
+        };
+        let pos = match shooter {
+            Some(pos) => pos,
+            None => return,
+        };
+
+        let shot = go::new_shot(pos + Vector2::new(0.0, -20.0), Vector2::new(0.0, -1.0), ecs::Faction::Invaders, &self.tuning);
+        self.entities.insert(shot);
+        self.events.push(evt::Event::ShotFired { faction: ecs::Faction::Invaders });
+    }
+
+    fn activate_player_shot(&mut self, slot: usize) { // Função que dispara um tiro do canhão
+        self.cannons[slot].shot_timeout = self.tuning.player_shot_time;
+
+        let pos = match self.get_cannon(slot) {
+            Some(player) => player.get_pos(),
+            None => return,
+        };
+        let shot = go::new_shot(pos + Vector2::new(0.0, 20.0), Vector2::new(0.0, 1.0), ecs::Faction::Player, &self.tuning);
+
+        self.entities.insert(shot);
+        self.events.push(evt::Event::ShotFired { faction: ecs::Faction::Player });
+    }
+
+    fn spawn_ufo(&mut self) { // Cria o disco voador em um dos lados da tela, indo para o outro
+        self.ufo_timer = self.tuning.ufo_spawn_time;
+
+        let start_x = (self.screen_width as f32) / 2.0 + go::UFO_MARGIN;
+        let (pos_x, direction) = if self.rng.gen::<bool>() {
+            (-start_x, Vector2::new(1.0, 0.0))
+        }
+        else {
+            (start_x, Vector2::new(-1.0, 0.0))
+        };
+
+        let ufo = go::new_ufo(Point2::new(pos_x, self.tuning.ufo_pos_y), direction, &self.tuning);
+        self.ufo = Some(self.entities.insert(ufo));
+    }
+
+    fn march_formation(&mut self) { // Acerta a velocidade e a direção da formação antes do movimento
+        let screen_bounds_x = (self.screen_width as f32) / 2.0 - GAME_BOUNDS;
+        let screen_bounds_y = (self.screen_height as f32) / 2.0 - GAME_BOUNDS;
+
+        // Velocidade dos inimigos aumenta inversamente proporcional ao número de inimigos
+        let enemy_speed = self.levels.get_level(self.level).get_enemy_speed(&self.tuning)/(self.formation_count() as f32);
+        // A animação acelera junto com a formação, que fica mais rápida conforme os inimigos morrem
+        let animation_rate = self.formation_size as f32 / (self.formation_count().max(1) as f32);
+        let mut reached_corner = false; // Se um inimigo encostar no limite horizontal da tela
+        let mut reached_bottom = false; // Se um inimigo sair da tela por baixo, o jogo acaba
+
+        for enemy in self.entities.iter_mut().filter(|entity| entity.formation.is_some()) {
+            if let Some(ref mut sprite) = enemy.sprite {
+                sprite.rate = animation_rate;
+            }
+            if let Some(ref mut velocity) = enemy.velocity {
+                velocity.speed = enemy_speed;
+            }
+
+            let pos = enemy.get_pos();
+            if pos.x.abs() > screen_bounds_x {
+                reached_corner = true;
+            }
+            if pos.y < -screen_bounds_y {
+                reached_bottom = true;
+            }
+        }
+
+        if reached_corner { // Move os inimigos pra baixo e troca sua direção
+            for enemy in self.entities.iter_mut().filter(|entity| entity.formation.is_some()) {
+                if let Some(ref mut position) = enemy.position {
+                    position.current.y -= 15.0;
+                }
+                if let Some(ref mut velocity) = enemy.velocity {
+                    velocity.direction.x = -velocity.direction.x;
+                }
+            }
+        }
+
+        if reached_bottom {
+            self.shared_lives = 0;
+            for cannon in &self.cannons {
+                if let Some(player) = self.entities.get_mut(cannon.id) {
+                    player.set_hit_points(0);
+                }
+            }
+        }
+    }
+
+    fn collisions(&mut self) { // Roda os sistemas de colisão, soma os pontos e gera os eventos do que foi atingido
+        let grid = sys::build_grid(&self.entities);
+        let report = sys::projectiles(&mut self.entities, &grid, self.tuning.barrier_blast_radius);
+        let crushed = sys::formation_contact(&mut self.entities, &grid);
+
+        for (pos, reward) in report.kills.into_iter().chain(crushed.kills) {
+            let base = match reward {
+                ecs::Reward::Points(points) => points,
+                ecs::Reward::RandomBonus => self.rng.choose(&self.tuning.ufo_scores).cloned().unwrap_or(0), // Bônus sorteado, como no arcade
+            };
+            let points = level_points(base, self.level, &self.tuning);
+            self.score += points;
+            self.events.push(evt::Event::EnemyKilled { pos, points });
+        }
+
+        if self.lives == Lives::Shared && !(report.player_hits.is_empty() && crushed.player_hits.is_empty()) { // Todos os canhões ficam com as vidas que sobraram
+            self.shared_lives = (self.shared_lives - report.player_hits.len() as i32).max(0);
+            if !crushed.player_hits.is_empty() { // Ser esmagado pela formação acaba com as vidas, como ela chegar ao chão
+                self.shared_lives = 0;
+            }
+            for cannon in &self.cannons {
+                if let Some(player) = self.entities.get_mut(cannon.id) {
+                    player.set_hit_points(self.shared_lives);
+                }
+            }
+        }
+        for slot in report.player_hits.into_iter().chain(crushed.player_hits) {
+            let hit_points = self.get_lives(slot);
+            let pos = self.get_cannon(slot).map_or(Point2::new(0.0, 0.0), |cannon| cannon.get_pos());
+            self.events.push(evt::Event::PlayerHit { slot, pos, hit_points });
+        }
+
+        for pos in report.masks_hit.into_iter().chain(crushed.masks_hit) {
+            self.events.push(evt::Event::BarrierDamaged { pos });
+        }
+    }
+
+    pub fn start_at_level(&mut self, level: i32) { // Pula direto para um level, só faz sentido antes do primeiro passo
+        self.level = level.max(0);
+        self.load_level();
+    }
+
+    fn load_level(&mut self) { // Troca a formação e as barreiras pelas do level atual
+        let level = self.levels.get_level(self.level); // O pacote repete o último level quando acaba
+        let new_enemies = level.create_enemies(self.screen_width, &self.tuning);
+        let new_barriers = level.create_barriers(&self.tuning);
+
+        // As barreiras (facção neutra) do level anterior são trocadas pelas novas
+        self.entities.retain(|entity| entity.formation.is_none() && entity.faction != Some(ecs::Faction::Neutral));
+        for entity in new_enemies.into_iter().chain(new_barriers) {
+            self.entities.insert(entity);
+        }
+        self.formation_size = self.formation_count();
+    }
+
+    fn check_for_level_respawn(&mut self) { // Recarrega os inimigos e as barreiras caso o level passe
+        if self.formation_count() == 0 {
+            self.events.push(evt::Event::LevelCleared { level: self.level });
+            self.level += 1;
+            self.load_level();
+        }
+    }
+
+    pub fn step(&mut self, seconds: f32, input: &InputState) { // Avança a simulação em um passo de tempo fixo
+
+        let xaxes: Vec<f32> = input.cannons.iter().map(|cannon| cannon.xaxis).collect();
+        sys::player_control(&mut self.entities, &xaxes);
+        for slot in 0..self.cannons.len() {
+            self.cannons[slot].shot_timeout -= seconds;
+            if input.cannons[slot].is_firing && self.cannons[slot].shot_timeout < 0.0 {
+                self.activate_player_shot(slot);
+            }
+        }
+        self.enemy_shot_timeout -= seconds;
+        if self.enemy_shot_timeout < 0.0 {
+            self.activate_enemy_shot();
+        }
+        self.ufo_timer -= seconds;
+        if self.ufo_timer < 0.0 && !self.has_ufo() {
+            self.spawn_ufo();
+        }
+
+        self.march_formation();
+        sys::movement(&mut self.entities, seconds);
+        sys::animate(&mut self.entities, seconds);
+        sys::confine_players(&mut self.entities, (self.screen_width as f32) / 2.0 - GAME_BOUNDS);
+
+        self.collisions();
+        sys::offscreen(&mut self.entities, self.screen_width as f32, self.screen_height as f32);
+        sys::cleanup(&mut self.entities);
+        self.check_for_level_respawn();
+        if self.is_game_over() && !self.game_over_sent {
+            self.game_over_sent = true;
+            self.events.push(evt::Event::GameOver { score: self.score, level: self.level });
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coop_world(lives: Lives) -> World {
+        let tuning = cfg::Tuning::default();
+        let levels = lvl::LevelPack::classic(480, &tuning);
+        return World::new(2, lives, 480, 640, 7, tuning, levels);
+    }
+
+    fn crush_cannon(world: &mut World, slot: usize) -> Vec<evt::Event> { // Põe um invasor em cima do canhão e roda um passo
+        let target = world.get_cannon(slot).unwrap().get_pos();
+        let invader = world.entities.ids().into_iter()
+            .find(|&id| world.entities.get(id).map_or(false, |entity| entity.formation.is_some()))
+            .unwrap();
+        if let Some(ref mut position) = world.entities.get_mut(invader).unwrap().position {
+            position.current = target;
+        }
+
+        world.step(1.0 / 60.0, &InputState::default());
+        return world.take_events();
+    }
+
+    fn crushed_slots(events: &[evt::Event]) -> Vec<(usize, i32)> {
+        return events.iter()
+            .filter_map(|event| match *event {
+                evt::Event::PlayerHit { slot, hit_points, .. } => Some((slot, hit_points)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    #[test]
+    fn crushed_cannon_ends_shared_lives() {
+        let mut world = coop_world(Lives::Shared);
+        let events = crush_cannon(&mut world, 1);
+
+        assert_eq!(crushed_slots(&events), vec![(1, 0)]);
+        assert_eq!(world.get_lives(0), 0);
+        assert!(world.get_cannon(0).is_none());
+        assert!(world.is_game_over());
+        assert_eq!(events.last(), Some(&evt::Event::GameOver { score: world.get_score(), level: 0 }));
+    }
+
+    #[test]
+    fn clearing_the_formation_reports_the_level() {
+        let mut world = coop_world(Lives::PerCannon);
+        for entity in world.entities.iter_mut().filter(|entity| entity.formation.is_some()) {
+            entity.set_hit_points(0);
+        }
+
+        world.step(1.0 / 60.0, &InputState::default());
+        assert!(world.take_events().contains(&evt::Event::LevelCleared { level: 0 }));
+        assert_eq!(world.get_level(), 1);
+    }
+
+    #[test]
+    fn crushed_cannon_keeps_the_other_cannon_lives() {
+        let mut world = coop_world(Lives::PerCannon);
+        let events = crush_cannon(&mut world, 1);
+
+        assert_eq!(crushed_slots(&events), vec![(1, 0)]);
+        assert_eq!(world.get_lives(0), world.get_tuning().player_hp);
+        assert!(world.get_cannon(1).is_none());
+        assert!(!world.is_game_over());
+    }
+
+    #[test]
+    fn fast_shot_hits_the_invader_it_jumps_over() {
+        let seconds = 1.0 / 60.0;
+        let tuning = cfg::Tuning {
+            shot_speed: 6000.0,      // 100 pixels por passo
+            enemy_shot_time: 1000.0, // Sem tiros inimigos nem disco voador atrapalhando
+            ufo_spawn_time: 1000.0,
+            ..cfg::Tuning::default()
+        };
+        assert!(tuning.shot_speed * seconds > tuning.enemy_hitbox[1] + tuning.shot_hitbox[1]);
+
+        let levels = lvl::LevelPack::classic(480, &tuning);
+        let mut world = World::new(1, Lives::PerCannon, 480, 640, 5, tuning, levels);
+        let cannon = world.get_cannon(0).unwrap().get_pos();
+
+        // Fica só um invasor, no meio do caminho que o tiro faz no primeiro passo (de -270 a -170)
+        let mut kept = false;
+        world.entities.retain(|entity| {
+            let keep = entity.player_control.is_some() || (entity.formation.is_some() && !kept);
+            kept = kept || entity.formation.is_some();
+            keep
+        });
+        for entity in world.entities.iter_mut().filter(|entity| entity.formation.is_some()) {
+            if let Some(ref mut position) = entity.position {
+                position.current = Point2::new(cannon.x - 5.0, -220.0);
+                position.previous = position.current;
+            }
+        }
+
+        let mut input = InputState::default();
+        input.cannons[0].is_firing = true;
+        world.step(seconds, &input);
+
+        let events = world.take_events();
+        assert!(events.iter().any(|event| matches!(*event, evt::Event::EnemyKilled { .. })));
+        assert!(events.contains(&evt::Event::LevelCleared { level: 0 }));
+    }
+}
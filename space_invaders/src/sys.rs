@@ -0,0 +1,371 @@
+// Sistemas da simulação. Cada sistema percorre as entidades e só age nas que
+// possuem os componentes de que ele precisa, sem olhar que "tipo" de objeto elas são.
+
+use ggez::graphics::{Point2, Vector2};
+
+use col;
+use ecs;
+
+const GRID_CELL_SIZE: f32 = 64.0; // Tamanho das células da fase ampla das colisões
+
+pub struct DamageReport { // O que os acertos de um passo causaram, para a simulação pontuar e gerar os eventos
+    pub kills: Vec<(Point2, ecs::Reward)>, // Entidades com recompensa destruídas, e onde
+    pub player_hits: Vec<usize>,           // Canhão de cada acerto em entidades controladas pelo jogador
+    pub masks_hit: Vec<Point2>,            // Pontos onde um tiro corroeu uma máscara
+}
+
+pub fn build_grid(entities: &ecs::EntityStore) -> col::Grid { // Fase ampla: grade com a caixa de cada entidade com collider, pelo slot
+    let mut grid = col::Grid::new(GRID_CELL_SIZE);
+    for id in entities.ids() {
+        if let Some(shape) = entities.get(id).and_then(|entity| entity.get_shape()) {
+            grid.insert(id.get_index(), &shape.get_bounds());
+        }
+    }
+    return grid;
+}
+
+fn mask_local(mask_bounds: &col::Aabb, area: &col::Aabb) -> (f32, f32, f32, f32) { // Passa uma caixa do mundo para as coordenadas da máscara
+    return (area.min.x - mask_bounds.min.x,
+        mask_bounds.max.y - area.max.y,
+        area.max.x - mask_bounds.min.x,
+        mask_bounds.max.y - area.min.y);
+}
+
+fn erode_by_shot(target: &mut ecs::Entity, shot_bounds: &col::Aabb, direction_y: f32, blast_radius: f32) -> bool { // Corrói a máscara onde o tiro encosta nela
+    let mask_bounds = match target.get_shape() {
+        Some(shape) => shape.get_bounds(),
+        None => return false,
+    };
+    if !mask_bounds.overlaps(shot_bounds) {
+        return false;
+    }
+
+    let (hit, empty) = match target.collider.as_mut().and_then(|collider| collider.mask.as_mut()) {
+        Some(mask) => {
+            let (left, top, right, bottom) = mask_local(&mask_bounds, shot_bounds);
+            let left = left.floor() as i32;
+            let right = right.ceil() as i32 - 1;
+            let top = top.floor() as i32;
+            let bottom = bottom.ceil() as i32 - 1;
+
+            // Procura a partir do lado de onde o tiro veio, para acertar o primeiro pixel no caminho
+            let impact = if direction_y > 0.0 {
+                mask.first_solid_in_column(left, right, bottom, top)
+            }
+            else {
+                mask.first_solid_in_column(left, right, top, bottom)
+            };
+
+            match impact {
+                Some((hit_x, hit_y)) => {
+                    mask.erode_circle(hit_x as f32 + 0.5, hit_y as f32 + 0.5, blast_radius);
+                    (true, mask.is_empty())
+                }
+                None => (false, false),
+            }
+        }
+        None => (false, false),
+    };
+
+    if empty {
+        target.set_hit_points(0);
+    }
+    return hit;
+}
+
+fn erode_by_contact(target: &mut ecs::Entity, area: &col::Aabb) -> bool { // Apaga toda a parte da máscara dentro da caixa, diz se algo foi apagado
+    let mask_bounds = match target.get_shape() {
+        Some(shape) => shape.get_bounds(),
+        None => return false,
+    };
+
+    let (eroded, empty) = match target.collider.as_mut().and_then(|collider| collider.mask.as_mut()) {
+        Some(mask) => {
+            let (left, top, right, bottom) = mask_local(&mask_bounds, area);
+            let eroded = mask.erode_rect(left, top, right, bottom) > 0;
+            (eroded, eroded && mask.is_empty())
+        }
+        None => (false, false),
+    };
+
+    if empty {
+        target.set_hit_points(0);
+    }
+    return eroded;
+}
+
+fn has_mask(entity: &ecs::Entity) -> bool {
+    return match entity.collider {
+        Some(ref collider) => collider.mask.is_some(),
+        None => false,
+    };
+}
+
+
+pub fn player_control(entities: &mut ecs::EntityStore, xaxes: &[f32]) { // Aponta cada canhão para onde o seu input manda
+    for entity in entities.iter_mut() {
+        let xaxis = match entity.player_control {
+            Some(ref control) => xaxes.get(control.slot).cloned().unwrap_or(0.0),
+            None => continue,
+        };
+        if let Some(ref mut velocity) = entity.velocity {
+            velocity.direction = Vector2::new(xaxis, 0.0);
+        }
+    }
+}
+
+pub fn movement(entities: &mut ecs::EntityStore, seconds: f32) { // Move tudo que tem posição e velocidade
+    for entity in entities.iter_mut() {
+        if let (Some(position), Some(velocity)) = (entity.position.as_mut(), entity.velocity.as_ref()) {
+            position.previous = position.current;
+            position.current += velocity.direction * velocity.speed * seconds;
+        }
+    }
+}
+
+pub fn animate(entities: &mut ecs::EntityStore, seconds: f32) { // Avança o tempo das animações
+    for entity in entities.iter_mut() {
+        if let Some(ref mut sprite) = entity.sprite {
+            sprite.time += seconds * sprite.rate;
+        }
+    }
+}
+
+pub fn confine_players(entities: &mut ecs::EntityStore, bounds_x: f32) { // Não deixa o jogador sair da tela
+    for entity in entities.iter_mut().filter(|entity| entity.player_control.is_some()) {
+        if let Some(ref mut position) = entity.position {
+            position.current.x = position.current.x.max(-bounds_x).min(bounds_x);
+        }
+    }
+}
+
+pub fn offscreen(entities: &mut ecs::EntityStore, screen_width: f32, screen_height: f32) { // Destrói o que saiu da tela
+    for entity in entities.iter_mut() {
+        let margin = match entity.offscreen {
+            Some(ref offscreen) => offscreen.margin,
+            None => continue,
+        };
+        let pos = entity.get_pos();
+        if pos.x.abs() > screen_width / 2.0 + margin || pos.y.abs() > screen_height / 2.0 + margin {
+            entity.set_hit_points(0);
+        }
+    }
+}
+
+pub fn projectiles(entities: &mut ecs::EntityStore, grid: &col::Grid, blast_radius: f32) -> DamageReport { // Acertos dos tiros, com teste contínuo
+    let mut report = DamageReport { kills: Vec::new(), player_hits: Vec::new(), masks_hit: Vec::new() };
+    let mut found = Vec::new();
+
+    for p in entities.ids() {
+        let (start, delta, damage, origin) = {
+            let shot = match entities.get(p) {
+                Some(shot) => shot,
+                None => continue,
+            };
+            let damage = match shot.projectile {
+                Some(ref projectile) if shot.is_alive() => projectile.damage,
+                _ => continue,
+            };
+            match (shot.get_sweep(), shot.position) {
+                (Some((start, delta)), Some(position)) => (start, delta, damage, position.previous),
+                _ => continue,
+            }
+        };
+
+        // Procura o primeiro alvo no caminho feito pelo tiro neste passo. Todos os tiros têm a mesma
+        // velocidade, então um tiro que vem de encontro está no máximo um deslocamento além do caminho
+        let path = start.union(&start.translate(delta));
+        grid.query(&path.union(&path.translate(-delta)), &mut found);
+
+        let mut first: Option<(f32, ecs::EntityId)> = None;
+        for &slot in &found {
+            let t = match entities.get_id(slot) {
+                Some(t) if t != p => t,
+                _ => continue,
+            };
+            let (shot, target) = match (entities.get(p), entities.get(t)) {
+                (Some(shot), Some(target)) => (shot, target),
+                _ => continue,
+            };
+            if !target.is_alive() || !shot.is_enemy_of(target) {
+                continue;
+            }
+
+            let time = match (target.projectile.is_some(), target.get_sweep(), target.get_shape()) {
+                (true, Some((target_start, target_delta)), _) => { // Dois tiros se movendo: usa o movimento relativo
+                    col::sweep(&start, delta - target_delta, &col::Shape::Box(target_start))
+                }
+                (false, _, Some(shape)) => col::sweep(&start, delta, &shape),
+                _ => None,
+            };
+
+            if let Some(time) = time {
+                let earlier = match first {
+                    Some((best, _)) => time < best,
+                    None => true,
+                };
+                if earlier { // No empate fica o primeiro na ordem dos slots
+                    first = Some((time, t));
+                }
+            }
+        }
+
+        let (time, t) = match first {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        let hit = match entities.get_mut(t) {
+            Some(ref mut target) if has_mask(target) => { // Tiro passa pelos buracos da máscara
+                let eroded = erode_by_shot(target, &start.translate(delta * time), delta.y, blast_radius);
+                if eroded {
+                    report.masks_hit.push(origin + delta * time);
+                }
+                eroded
+            }
+            Some(target) => {
+                let hit_points = target.get_hit_points() - damage;
+                target.set_hit_points(hit_points);
+                if let Some(ref control) = target.player_control {
+                    report.player_hits.push(control.slot);
+                }
+                if !target.is_alive() {
+                    if let Some(reward) = target.reward {
+                        report.kills.push((target.get_pos(), reward));
+                    }
+                }
+                true
+            }
+            None => false,
+        };
+
+        if hit {
+            if let Some(shot) = entities.get_mut(p) {
+                shot.set_hit_points(0);
+            }
+        }
+    }
+    return report;
+}
+
+pub fn formation_contact(entities: &mut ecs::EntityStore, grid: &col::Grid) -> DamageReport { // A formação esmaga o que encosta: corrói máscaras e mata o resto
+    let mut found = Vec::new();
+    let mut report = DamageReport { kills: Vec::new(), player_hits: Vec::new(), masks_hit: Vec::new() };
+
+    for b in entities.ids() {
+        let bounds = match entities.get(b) {
+            Some(body) if body.formation.is_some() && body.is_alive() => match body.get_shape() {
+                Some(shape) => shape.get_bounds(),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        grid.query(&bounds, &mut found);
+        for &slot in &found {
+            let t = match entities.get_id(slot) {
+                Some(t) if t != b => t,
+                _ => continue,
+            };
+            let touching = match (entities.get(b), entities.get(t)) {
+                (Some(body), Some(target)) => {
+                    target.is_alive() && target.projectile.is_none() && body.is_enemy_of(target)
+                        && match (body.get_shape(), target.get_shape()) {
+                            (Some(a), Some(b)) => col::overlaps(&a, &b),
+                            _ => false,
+                        }
+                }
+                _ => false,
+            };
+
+            if touching {
+                if let Some(target) = entities.get_mut(t) {
+                    if has_mask(target) {
+                        if erode_by_contact(target, &bounds) {
+                            report.masks_hit.push(target.get_pos()); // Posição da máscara, não do ponto de contato
+                        }
+                    }
+                    else {
+                        target.set_hit_points(0);
+                        if let Some(ref control) = target.player_control {
+                            report.player_hits.push(control.slot);
+                        }
+                        if let Some(reward) = target.reward {
+                            report.kills.push((target.get_pos(), reward));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    return report;
+}
+
+pub fn cleanup(entities: &mut ecs::EntityStore) { // Remove as entidades destruídas, liberando os slots
+    entities.retain(|entity| entity.is_alive());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cfg;
+    use go;
+
+    fn fire_through(entities: &mut ecs::EntityStore, x: f32, tuning: &cfg::Tuning) -> (ecs::EntityId, DamageReport) { // Tiro que atravessa a origem de baixo para cima num passo
+        let mut shot = go::new_shot(Point2::new(x, -30.0), Vector2::new(0.0, 1.0), ecs::Faction::Player, tuning);
+        if let Some(ref mut position) = shot.position {
+            position.current = Point2::new(x, 30.0);
+        }
+        let id = entities.insert(shot);
+        let grid = build_grid(entities);
+        let report = projectiles(entities, &grid, tuning.barrier_blast_radius);
+        return (id, report);
+    }
+
+    fn solid_pixels(entities: &ecs::EntityStore, barrier: ecs::EntityId) -> usize {
+        let mask = entities.get(barrier).unwrap().collider.as_ref().unwrap().mask.as_ref().unwrap();
+        let mut count = 0;
+        for y in 0..mask.get_height() as i32 {
+            for x in 0..mask.get_width() as i32 {
+                if mask.is_solid(x, y) {
+                    count += 1;
+                }
+            }
+        }
+        return count;
+    }
+
+    #[test]
+    fn shot_clears_barrier_pixels() {
+        let tuning = cfg::Tuning::default();
+        let mut entities = ecs::EntityStore::new();
+        let barrier = entities.insert(go::new_barrier(Point2::new(0.0, 0.0), &tuning));
+        let before = solid_pixels(&entities, barrier);
+
+        let (shot, report) = fire_through(&mut entities, 0.0, &tuning);
+
+        assert_eq!(report.masks_hit.len(), 1);
+        assert!(solid_pixels(&entities, barrier) < before);
+        assert!(!entities.get(shot).unwrap().is_alive());
+    }
+
+    #[test]
+    fn second_shot_passes_through_the_hole() {
+        let tuning = cfg::Tuning { // Barreira fina, que o primeiro tiro fura de lado a lado
+            barrier_hitbox: [32.0, 4.0],
+            barrier_blast_radius: 6.0,
+            ..cfg::Tuning::default()
+        };
+        let mut entities = ecs::EntityStore::new();
+        let barrier = entities.insert(go::new_barrier(Point2::new(0.0, 0.0), &tuning));
+
+        let (_, first) = fire_through(&mut entities, 0.0, &tuning);
+        assert_eq!(first.masks_hit.len(), 1);
+        cleanup(&mut entities);
+
+        let (shot, second) = fire_through(&mut entities, 0.0, &tuning);
+        assert!(second.masks_hit.is_empty());
+        assert!(entities.get(shot).unwrap().is_alive());
+        assert!(entities.get(barrier).unwrap().is_alive());
+    }
+}
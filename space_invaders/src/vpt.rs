@@ -0,0 +1,97 @@
+// Área de jogo lógica de tamanho fixo, escalada para qualquer tamanho de janela.
+// A simulação e o desenho sempre usam WIDTH x HEIGHT; só a projeção muda com a
+// janela, com faixas pretas (letterbox) onde a proporção não bate. No modo pixel
+// perfect a escala é inteira, para cada pixel das sprites virar um quadrado igual.
+
+use ggez::{Context, GameResult};
+use ggez::conf::FullscreenType;
+use ggez::graphics::{self, Rect};
+
+pub const WIDTH: u32 = 480; // Tamanho lógico da área de jogo, também o tamanho inicial da janela
+pub const HEIGHT: u32 = 640;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    window_width: u32,
+    window_height: u32,
+    integer_scale: bool,
+    fullscreen: bool,
+}
+
+impl Viewport {
+    pub fn new(window_width: u32, window_height: u32, fullscreen: bool) -> Viewport {
+        Viewport {
+            window_width,
+            window_height,
+            integer_scale: false,
+            fullscreen,
+        }
+    }
+
+    pub fn is_integer_scale(&self) -> bool {
+        return self.integer_scale;
+    }
+
+    pub fn set_integer_scale(&mut self, integer_scale: bool) {
+        self.integer_scale = integer_scale;
+    }
+
+    pub fn resize(&mut self, window_width: u32, window_height: u32) {
+        self.window_width = window_width.max(1);
+        self.window_height = window_height.max(1);
+    }
+
+    pub fn get_scale(&self) -> f32 { // Pixels da janela por unidade lógica
+        let scale = (self.window_width as f32 / WIDTH as f32).min(self.window_height as f32 / HEIGHT as f32);
+        // Janela menor que a área de jogo não tem escala inteira, então encolhe normalmente
+        if self.integer_scale && scale >= 1.0 {
+            return scale.floor();
+        }
+        return scale;
+    }
+
+    pub fn get_screen_rect(&self) -> Rect { // Coordenadas lógicas que a janela inteira mostra, com a área de jogo centralizada
+        let scale = self.get_scale();
+        // As faixas são arredondadas para pixels inteiros da janela, senão as sprites ficam borradas
+        let bar_x = ((self.window_width as f32 - WIDTH as f32 * scale) / 2.0).floor();
+        let bar_y = ((self.window_height as f32 - HEIGHT as f32 * scale) / 2.0).floor();
+
+        Rect::new(
+            -bar_x / scale,
+            -bar_y / scale,
+            self.window_width as f32 / scale,
+            self.window_height as f32 / scale,
+        )
+    }
+
+    pub fn get_bars(&self) -> Vec<Rect> { // Partes visíveis fora da área de jogo, em coordenadas lógicas
+        let screen = self.get_screen_rect();
+        let (width, height) = (WIDTH as f32, HEIGHT as f32);
+        let mut bars = Vec::new();
+
+        if screen.x < 0.0 {
+            bars.push(Rect::new(screen.x, screen.y, -screen.x, screen.h));
+            bars.push(Rect::new(width, screen.y, screen.x + screen.w - width, screen.h));
+        }
+        if screen.y < 0.0 {
+            bars.push(Rect::new(screen.x, screen.y, screen.w, -screen.y));
+            bars.push(Rect::new(screen.x, height, screen.w, screen.y + screen.h - height));
+        }
+        return bars;
+    }
+
+    pub fn apply(&self, ctx: &mut Context) -> GameResult<()> { // Atualiza a projeção depois de qualquer mudança
+        graphics::set_screen_coordinates(ctx, self.get_screen_rect())
+    }
+
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult<()> { // Tela cheia na resolução do desktop, sem trocar o modo de vídeo
+        let mut mode = ctx.conf.window_mode;
+        mode.fullscreen_type = if self.fullscreen { FullscreenType::Off } else { FullscreenType::Desktop };
+        graphics::set_mode(ctx, mode)?;
+        self.fullscreen = !self.fullscreen;
+
+        let (width, height) = graphics::get_size(ctx);
+        self.resize(width, height);
+        self.apply(ctx)
+    }
+}